@@ -0,0 +1,63 @@
+#![no_std]
+// Compiles every macro of rs_ervice without std, so generated code naming `::std` fails the build.
+
+use rs_ervice::{impl_rs_service, RSContext, RSContextBuilder, RsServiceError};
+use rs_ervice_macro_lib::{r_service, r_service_struct, RSContextService};
+
+#[derive(Default, RSContextService)]
+#[rservice(name = "derived")]
+struct DerivedService {}
+
+#[derive(RSContextService)]
+#[rservice(new)]
+struct DerivedWithNewService {}
+
+impl DerivedWithNewService {
+    fn new() -> Self {
+        DerivedWithNewService {}
+    }
+}
+
+#[r_service_struct(name = "generated")]
+struct GeneratedService {}
+
+#[r_service(auto_impl, depends_on = "DerivedService")]
+impl GeneratedService {
+    fn new() -> Self {
+        GeneratedService {}
+    }
+}
+
+#[r_service_struct]
+struct UnifiedService {}
+
+#[r_service]
+impl UnifiedService {
+    fn new() -> Self {
+        UnifiedService {}
+    }
+}
+
+impl_rs_service! {
+    impl UnifiedService {
+        fn on_register_crate_instance() -> Self {
+            UnifiedService::new()
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+    }
+}
+
+/// Registers every service above and builds the context.
+pub fn build() -> Result<RSContext, RsServiceError> {
+    RSContextBuilder::new()
+        .register::<GeneratedService>()?
+        .register::<DerivedService>()?
+        .register::<DerivedWithNewService>()?
+        .register::<UnifiedService>()?
+        .build()
+}
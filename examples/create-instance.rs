@@ -1,10 +1,4 @@
-use std::any::Any;
-#[cfg(feature = "tokio")]
-use rs_ervice::tokio_rs_ervice::{AsyncHooksResult,RSContextService,RSContextBuilder};
-
-#[cfg(not(feature = "tokio"))]
-use rs_ervice::{common::RsServiceError, vanilla_rs_ervice::{RSContextBuilder, RSContextService}};
-use rs_ervice_macro_lib::{r_service, r_service_struct};
+use rs_ervice::prelude::*;
 #[r_service_struct]
 #[derive(Debug, Clone)]
 struct MyService {
@@ -23,11 +17,11 @@ impl MyService  {
             state : "HELLO - ".to_string(),
         }
     }
-    #[cfg(feature = "tokio")]
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
     pub async fn doing_something(&self, something: String) -> String {
         self.state.clone() + &something
     }
-    #[cfg(not(feature = "tokio"))]
+    #[cfg(not(any(feature = "tokio", feature = "async-std")))]
     pub fn doing_something(&self, something: String) -> String {
         self.state.clone() + &something
     }
@@ -40,39 +34,22 @@ impl Chant for MyService {
     }
 }
 
-#[cfg(not(feature = "tokio"))]
-impl RSContextService for MyService {
-    fn on_register_crate_instance() -> Self {
-        MyService::new()
-    }
-    
-    fn on_service_created(&mut self, service_builder: &RSContextBuilder) -> Result<(), RsServiceError> {
-        // 서비스가 등록될 때 호출되는 메서드
-        println!("Service {} registered successfully!", std::any::type_name::<Self>());
-        Ok(())
-    }
-    fn on_all_services_built(&self, context: &rs_ervice::RSContext) -> Result<(), RsServiceError> {
-        // 모든 서비스가 빌드된 후 호출되는 메서드
-        println!("All services built successfully in context: {:?}", context.type_id());
-        Ok(())
-    }
-}
-
-#[cfg(feature = "tokio")]
-impl RSContextService for MyService {
-    async fn on_register_crate_instance() -> Self {
-        MyService::new()
-    }
-    
-    async fn on_service_created(&mut self, service_builder: &RSContextBuilder) -> Result<(), RsServiceError> {
-        // 서비스가 등록될 때 호출되는 메서드
-        println!("Service {} registered successfully!", std::any::type_name::<Self>());
-        Ok(())
-    }
-    async fn on_all_services_built(&self, context: &rs_ervice::RSContext) -> Result<(), RsServiceError> {
-        // 모든 서비스가 빌드된 후 호출되는 메서드
-        println!("All services built successfully in context: {:?}", context.type_id());
-        Ok(())
+// The hooks are written once; impl_rs_service! makes them async on tokio and async-std.
+impl_rs_service! {
+    impl MyService {
+        fn on_register_crate_instance() -> Self {
+            MyService::new()
+        }
+        fn on_service_created(&mut self, _service_builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            // 서비스가 등록될 때 호출되는 메서드
+            println!("Service {} registered successfully!", std::any::type_name::<Self>());
+            Ok(())
+        }
+        fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+            // 모든 서비스가 빌드된 후 호출되는 메서드
+            println!("All services built successfully in context: {:?}", context);
+            Ok(())
+        }
     }
 }
 
@@ -82,54 +59,20 @@ struct AnotherService {
 
 }
 
-#[r_service]
+#[r_service(auto_impl)]
 impl AnotherService {
     pub fn new() -> Self {
         AnotherService {}
     }
 }
-#[cfg(not(feature = "tokio"))]
-impl RSContextService for AnotherService {
-    fn on_register_crate_instance() -> Self {
-        AnotherService::new()
-    }
-    
-    fn on_service_created(&mut self, service_builder: &RSContextBuilder) -> Result<(), RsServiceError> {
-        print!("AnotherService registered!\n");
-        Ok(())
-    }
-    fn on_all_services_built(&self, context: &rs_ervice::RSContext) -> Result<(), RsServiceError> {
-        // 모든 서비스가 빌드된 후 호출되는 메서드
-        println!("All services built successfully in context: {:?}", context.type_id());
-        Ok(())
-    }
-}
-#[cfg(feature = "tokio")]
-impl RSContextService for AnotherService {
-    async fn on_register_crate_instance() -> Self {
-        AnotherService::new()
-    }
-    
-    async fn on_service_created(&mut self, _service_builder: &RSContextBuilder) -> Result<(), RsServiceError> {
-        print!("AnotherService registered!\n");
-        Ok(())
-    }
-    async fn on_all_services_built(&self, context: &rs_ervice::RSContext)->AsyncHooksResult {
-        // 모든 서비스가 빌드된 후 호출되는 메서드
-        println!("All services built successfully in context: {:?}", context.type_id());
-        Ok(())
-    }
-}
+// RSContextService for AnotherService is generated by #[r_service(auto_impl)] from `new()`
 
-/// on use...
+// on use...
 
 
 
-#[cfg(not(feature = "tokio"))]
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
 fn main(){
-    use rs_ervice::RSContext;
-
-
     fn build_context() -> Result<RSContext, RsServiceError> {
         Ok(
             RSContextBuilder::new()
@@ -140,12 +83,10 @@ fn main(){
             )
     }
     let service_context = build_context().expect("Failed to create RSContext");
-    let do_service = service_context.call::<MyService>();
-
-    if do_service.is_none() {
-        panic!("MyService is not registered!");
-    }
-    let do_service = do_service.unwrap().lock().unwrap().doing_something("Hi!".to_string());
+    // call_map locks the service the same way whichever mutex backs it (std, spin or parking_lot).
+    let do_service = service_context
+        .call_map::<MyService, _>(|service| service.doing_something("Hi!".to_string()))
+        .expect("MyService is not registered!");
 
 
     
@@ -160,20 +101,18 @@ fn main(){
 
 }
 
-#[cfg(feature = "tokio")]
-#[tokio::main]
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::main)]
+#[cfg_attr(feature = "async-std", async_std::main)]
 async fn main() {
-    use rs_ervice::RSContext;
     async fn build_context() -> Result<RSContext, RsServiceError> {
-        Ok(
-            RSContextBuilder::new()
-                .register::<MyService>()
-                .await?
-                .register::<AnotherService>()
-                .await?
-                .build()
-                .await?
-            )
+        RSContextBuilder::new()
+            .register::<MyService>()
+            .await?
+            .register::<AnotherService>()
+            .await?
+            .build()
+            .await
     }
     let service_context = build_context().await.expect("Failed to build RSContext");
 
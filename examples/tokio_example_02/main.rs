@@ -3,7 +3,24 @@ mod services;
 #[cfg(feature = "tokio")]
 #[tokio::main]
 async fn main() {
+    use rs_ervice::RSContextBuilder;
+    use services::write_file::WriteFileService;
 
+    let context = RSContextBuilder::new()
+        .register::<WriteFileService>()
+        .await
+        .expect("Failed to register WriteFileService")
+        .build()
+        .await
+        .expect("Failed to build RSContext");
+
+    let file_path = std::env::temp_dir().join("rs_ervice_tokio_example_02.txt");
+    context
+        .call_map(|service: &mut WriteFileService| service.open_file(&file_path.to_string_lossy()))
+        .await
+        .expect("WriteFileService is not registered")
+        .expect("Failed to open the file");
+    println!("Wrote {}", file_path.display());
 }
 
 #[cfg(not(feature = "tokio"))]
@@ -1,18 +1,17 @@
 use rs_ervice::RSContextService;
-use std::any::Any;
 use std::fs::File;
-use std::io::{self, Write};
+use std::io::Write;
 
 
 pub struct WriteFileService{
     now_file_path: String,
     cl_lf: bool,
-    stream: Option<Box<dyn Write>>
+    stream: Option<Box<dyn Write + Send + Sync>>
 }
 
 impl WriteFileService{
 
-    pub fn open_file(&mut self, file_path: &str) -> io::Result<()> {
+    pub fn open_file(&mut self, file_path: &str) -> Result<(), rs_ervice::RsServiceError> {
         self.now_file_path = file_path.to_string();
         let mut file = File::create(&self.now_file_path)?;
         if self.cl_lf {
@@ -20,12 +19,15 @@ impl WriteFileService{
         } else {
             write!(file, "File opened at: {}", self.now_file_path)?;
         }
+        self.stream = Some(Box::new(file));
         Ok(())
     }
 
 }
 
 impl RSContextService for WriteFileService{
+    type Error = rs_ervice::RsServiceError;
+
     async fn on_register_crate_instance() -> Self {
         WriteFileService {
             now_file_path: String::new(),
@@ -34,7 +36,7 @@ impl RSContextService for WriteFileService{
         }
     }
 
-    async fn on_service_created(&mut self, service_builder: &rs_ervice::RSContextBuilder) -> Result<(), rs_ervice::RsServiceError> {
+    async fn on_service_created(&mut self, _service_builder: &mut rs_ervice::RSContextBuilder) -> Result<(), rs_ervice::RsServiceError> {
         // 서비스가 등록될 때 호출되는 메서드
         println!("WriteFileService registered successfully!");
         Ok(())
@@ -42,7 +44,7 @@ impl RSContextService for WriteFileService{
 
     async fn on_all_services_built(&self, context: &rs_ervice::RSContext) -> Result<(), rs_ervice::RsServiceError> {
         // 모든 서비스가 빌드된 후 호출되는 메서드
-        println!("All services built successfully in context: {:?}", context.type_id());
+        println!("All services built successfully in context: {:?}", context);
         Ok(())
     }
 }
\ No newline at end of file
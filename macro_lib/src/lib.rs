@@ -1,59 +1,281 @@
 use proc_macro::TokenStream;
-use syn::{ItemStruct,parse_macro_input,ItemImpl};
+use syn::{DeriveInput,Expr,ExprLit,Generics,ImplItem,Item,ItemImpl,Lit,LitStr,Meta,ReturnType,Token,Type,parse_macro_input,parse_quote};
+use syn::punctuated::Punctuated;
 use quote::quote;
 // --- #[r_service_struct] 매크로 ---
 
-// tokio feature가 활성화된 경우
+/// 구조체(튜플 구조체 포함) 또는 열거형에 붙이면 서비스 이름 상수 `RS_SERVICE_NAME` 과 모듈 경로 상수 `RS_SERVICE_MODULE_PATH` 를 생성합니다.
+/// `#[r_service_struct(name = "foo")]` 로 이름을 지정하지 않으면 타입 이름을 사용합니다.
 #[proc_macro_attribute]
-#[cfg(feature = "tokio")]
-pub fn r_service_struct(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    // tokio 환경에 맞는 코드 생성 (아직 구현되지 않음)
-    // TODO: tokio용 코드 작성
-    item
-}
-
-// tokio feature가 비활성화된 경우
-#[proc_macro_attribute]
-#[cfg(not(feature = "tokio"))]
-pub fn r_service_struct(_attr: TokenStream, item: TokenStream) -> TokenStream {
-    // attr: 애트리뷰트에 전달된 인자 (예: #[r_service_struct(name = "foo")]) -> 현재는 사용 안 함
-    // item: 애트리뷰트가 붙은 아이템 (구조체 정의)
+pub fn r_service_struct(attr: TokenStream, item: TokenStream) -> TokenStream {
+    // attr: 애트리뷰트에 전달된 인자 (예: #[r_service_struct(name = "foo")])
+    // item: 애트리뷰트가 붙은 아이템 (구조체 또는 열거형 정의)
 
     // 1. 입력 파싱
-    let input_struct = parse_macro_input!(item as ItemStruct);
-    let struct_name = &input_struct.ident; // 구조체 이름 (예: MyService)
+    let args = parse_macro_input!(attr with Punctuated::<Meta, Token![,]>::parse_terminated);
+    let input_item = parse_macro_input!(item as Item);
+    let (struct_name, generics) = match &input_item {
+        Item::Struct(item_struct) => (&item_struct.ident, &item_struct.generics),
+        Item::Enum(item_enum) => (&item_enum.ident, &item_enum.generics),
+        other => {
+            return syn::Error::new_spanned(other, "#[r_service_struct] can only be used on structs or enums")
+                .to_compile_error()
+                .into();
+        }
+    }; // 타입 이름 (예: MyService)
+
+    // 2. name 인자 파싱 (없으면 구조체 이름, 알 수 없는 인자는 오류)
+    if let Err(err) = reject_unknown_args(&args, "r_service_struct", |meta| {
+        matches!(meta, Meta::NameValue(name_value) if name_value.path.is_ident("name"))
+    }) {
+        return err.to_compile_error().into();
+    }
+    let service_name = match find_name_arg(&args) {
+        Ok(Some(name)) => name,
+        Ok(None) => LitStr::new(&struct_name.to_string(), struct_name.span()),
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    // 2. 코드 생성
+    // 3. 코드 생성 (아이템은 그대로 출력)
     let expanded = quote! {
-        #input_struct
-        // 추가 코드 생성 가능
+        #input_item
+        impl #impl_generics #struct_name #ty_generics #where_clause {
+            /// Logical service name set by `#[r_service_struct(name = "...")]`.
+            pub const RS_SERVICE_NAME: &'static str = #service_name;
+            /// Rust module path the service is defined in (e.g. `my_crate::services::write_file`).
+            pub const RS_SERVICE_MODULE_PATH: &'static str = module_path!();
+        }
     };
 
-    // 3. 생성된 코드 반환
+    // 4. 생성된 코드 반환
     TokenStream::from(expanded)
 }
 
-// --- #[r_service] 매크로 ---
+// 알 수 없는(또는 오타가 난) 인자가 있으면 그 인자를 가리키는 오류를 반환
+fn reject_unknown_args(args: &Punctuated<Meta, Token![,]>, macro_name: &str, is_known: impl Fn(&Meta) -> bool) -> syn::Result<()> {
+    match args.iter().find(|meta| !is_known(meta)) {
+        Some(arg) => Err(syn::Error::new_spanned(arg, format!("unknown {macro_name} argument"))),
+        None => Ok(()),
+    }
+}
 
-// tokio feature가 활성화된 경우
-#[proc_macro_attribute]
-#[cfg(feature = "tokio")]
-pub fn r_service(attr: TokenStream, item: TokenStream) -> TokenStream {
-    // tokio 환경에 맞는 코드 생성 (service 모듈 사용)
-    service::r_service(attr, item)
+// `name = "..."` 인자를 찾아 문자열 리터럴로 반환
+fn find_name_arg(args: &Punctuated<Meta, Token![,]>) -> syn::Result<Option<LitStr>> {
+    for meta in args {
+        if let Meta::NameValue(name_value) = meta
+            && name_value.path.is_ident("name")
+        {
+            return match &name_value.value {
+                Expr::Lit(ExprLit { lit: Lit::Str(name), .. }) => Ok(Some(name.clone())),
+                other => Err(syn::Error::new_spanned(other, "expected a string literal, e.g. name = \"foo\"")),
+            };
+        }
+    }
+    Ok(None)
 }
 
-// tokio feature가 비활성화된 경우
+// --- #[r_service] 매크로 ---
+
+/// `impl` 블록에 붙입니다. 인자가 없으면 블록을 그대로 두므로 `RSContextService` 는 직접 구현합니다.
+/// `#[r_service(auto_impl)]` 로 지정하면 `new()` 생성자를 찾아 기본 `RSContextService` 구현을 생성합니다.
+/// 생성된 `name()` 은 `#[r_service_struct]` 의 `RS_SERVICE_NAME` 을 반환하며, 구조체 매크로가 없으면 타입 이름(`type_name`)을 반환합니다.
+/// `#[r_service(auto_impl, depends_on = "ServiceA, ServiceB")]` 로 지정하면 해당 타입들의 TypeId 를 반환하는 `dependencies()` 도 생성합니다.
 #[proc_macro_attribute]
-#[cfg(not(feature = "tokio"))]
-pub fn r_service(_attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn r_service(attr: TokenStream, item: TokenStream) -> TokenStream {
+    // 1. 입력 파싱
+    let args = parse_macro_input!(attr with Punctuated::<Meta, Token![,]>::parse_terminated);
     let input_impl = parse_macro_input!(item as ItemImpl);
 
+    // 2. auto_impl / depends_on 인자 파싱 (알 수 없는 인자는 오류)
+    if let Err(err) = reject_unknown_args(&args, "r_service", |meta| match meta {
+        Meta::Path(path) => path.is_ident("auto_impl"),
+        Meta::NameValue(name_value) => name_value.path.is_ident("depends_on"),
+        Meta::List(_) => false,
+    }) {
+        return err.to_compile_error().into();
+    }
+    let dependencies = match find_depends_on_arg(&args) {
+        Ok(dependencies) => dependencies,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    // 3. 코드 생성 (auto_impl 을 지정한 경우에만 생성)
+    let auto_impl = args.iter().find(|meta| meta.path().is_ident("auto_impl"));
+    let service_impl = match auto_impl {
+        Some(auto_impl) => {
+            if input_impl.trait_.is_some() || !has_new_constructor(&input_impl) {
+                return syn::Error::new_spanned(auto_impl, "auto_impl needs an inherent impl block with `fn new() -> Self`")
+                    .to_compile_error()
+                    .into();
+            }
+            let self_ty = &input_impl.self_ty;
+            let mut service_impl = generate_service_impl(self_ty, &input_impl.generics, &parse_quote!(<#self_ty>::new()));
+            // 로그에 쓰일 이름은 #[r_service_struct] 가 만든 RS_SERVICE_NAME
+            // 고유 상수가 없으면 빈 문자열을 주는 트레이트 기본값으로 해석되어 type_name 으로 대체
+            service_impl.items.push(parse_quote! {
+                fn name() -> &'static str {
+                    #[allow(unused_imports)]
+                    use ::rs_ervice::__private::ServiceNameFallback as _;
+                    match Self::RS_SERVICE_NAME {
+                        "" => ::core::any::type_name::<Self>(),
+                        name => name,
+                    }
+                }
+            });
+            if !dependencies.is_empty() {
+                service_impl.items.push(parse_quote! {
+                    fn dependencies() -> ::rs_ervice::__private::Vec<::core::any::TypeId> {
+                        ::rs_ervice::__private::vec![#(::core::any::TypeId::of::<#dependencies>()),*]
+                    }
+                });
+            }
+            Some(service_impl)
+        }
+        // 구현을 생성하지 않으면 depends_on 을 적용할 곳이 없음
+        None => match dependencies.first() {
+            Some(dependency) => {
+                return syn::Error::new_spanned(dependency, "depends_on needs the generated impl; add auto_impl, or override `dependencies()` in your own impl")
+                    .to_compile_error()
+                    .into();
+            }
+            None => None,
+        },
+    };
+
     let expanded = quote! {
         #input_impl
-        // 추가 코드 생성 가능
+        #service_impl
     };
 
+    // 4. 생성된 코드 반환
     TokenStream::from(expanded)
 }
 
+// `depends_on = "A, B"` 인자를 찾아 쉼표로 구분된 타입 목록으로 반환 (없으면 빈 목록)
+fn find_depends_on_arg(args: &Punctuated<Meta, Token![,]>) -> syn::Result<Vec<Type>> {
+    for meta in args {
+        if let Meta::NameValue(name_value) = meta
+            && name_value.path.is_ident("depends_on")
+        {
+            return match &name_value.value {
+                Expr::Lit(ExprLit { lit: Lit::Str(list), .. }) => Ok(list
+                    .parse_with(Punctuated::<Type, Token![,]>::parse_terminated)?
+                    .into_iter()
+                    .collect()),
+                other => Err(syn::Error::new_spanned(other, "expected a string literal, e.g. depends_on = \"ServiceA, ServiceB\"")),
+            };
+        }
+    }
+    Ok(Vec::new())
+}
+
+// --- #[derive(RSContextService)] 매크로 ---
+
+/// 구조체/열거형에 붙이면 훅이 비어 있는 기본 `RSContextService` 구현을 생성합니다.
+/// 인스턴스는 `Default::default()` 로 만들며, `#[rservice(new)]` 로 지정하면 `Self::new()` 를 사용합니다.
+/// `#[rservice(name = "foo")]` 로 지정하면 `name()` 이 그 이름을 반환합니다.
+#[proc_macro_derive(RSContextService, attributes(rservice))]
+pub fn derive_rs_context_service(item: TokenStream) -> TokenStream {
+    // 1. 입력 파싱
+    let input = parse_macro_input!(item as DeriveInput);
+
+    // 2. #[rservice(new, name = "...")] 인자 파싱
+    let mut use_new = false;
+    let mut service_name: Option<LitStr> = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("rservice")) {
+        let parsed = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("new") {
+                use_new = true;
+                Ok(())
+            } else if meta.path.is_ident("name") {
+                service_name = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `new` or `name = \"...\"`, e.g. #[rservice(new)]"))
+            }
+        });
+        if let Err(err) = parsed {
+            return err.to_compile_error().into();
+        }
+    }
+
+    // 3. 코드 생성
+    let ident = &input.ident;
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let self_ty: Type = parse_quote!(#ident #ty_generics);
+    let constructor: Expr = if use_new {
+        parse_quote!(Self::new())
+    } else {
+        parse_quote!(<Self as ::core::default::Default>::default())
+    };
+    let mut service_impl = generate_service_impl(&self_ty, &input.generics, &constructor);
+    if let Some(service_name) = service_name {
+        service_impl.items.push(parse_quote! {
+            fn name() -> &'static str {
+                #service_name
+            }
+        });
+    }
+
+    // 4. 생성된 코드 반환
+    TokenStream::from(quote! { #service_impl })
+}
+
+// 인자가 없고 `Self`(또는 자기 타입)를 반환하는 `fn new()` 가 있는지 확인
+fn has_new_constructor(input_impl: &ItemImpl) -> bool {
+    input_impl.items.iter().any(|item| match item {
+        ImplItem::Fn(method) => {
+            let sig = &method.sig;
+            sig.ident == "new"
+                && sig.inputs.is_empty()
+                && match &sig.output {
+                    ReturnType::Type(_, ty) => match ty.as_ref() {
+                        Type::Path(path) => path.path.is_ident("Self") || **ty == *input_impl.self_ty,
+                        _ => false,
+                    },
+                    ReturnType::Default => false,
+                }
+        }
+        _ => false,
+    })
+}
+
+// tokio, async-std feature가 비활성화된 경우: 동기 hook 생성
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+fn generate_service_impl(self_ty: &Type, generics: &Generics, constructor: &Expr) -> ItemImpl {
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    parse_quote! {
+        impl #impl_generics ::rs_ervice::RSContextService for #self_ty #where_clause {
+            fn on_register_crate_instance() -> Self {
+                #constructor
+            }
+            fn on_service_created(&mut self, _builder: &mut ::rs_ervice::RSContextBuilder) -> ::core::result::Result<(), ::rs_ervice::RsServiceError> {
+                Ok(())
+            }
+            fn on_all_services_built(&self, _context: &::rs_ervice::RSContext) -> ::core::result::Result<(), ::rs_ervice::RsServiceError> {
+                Ok(())
+            }
+        }
+    }
+}
+
+// tokio 또는 async-std feature가 활성화된 경우: async hook 생성
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+fn generate_service_impl(self_ty: &Type, generics: &Generics, constructor: &Expr) -> ItemImpl {
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    parse_quote! {
+        impl #impl_generics ::rs_ervice::RSContextService for #self_ty #where_clause {
+            type Error = ::rs_ervice::RsServiceError;
+            async fn on_register_crate_instance() -> Self {
+                #constructor
+            }
+            async fn on_service_created(&mut self, _builder: &mut ::rs_ervice::RSContextBuilder) -> ::core::result::Result<(), ::rs_ervice::RsServiceError> {
+                Ok(())
+            }
+            async fn on_all_services_built(&self, _context: &::rs_ervice::RSContext) -> ::core::result::Result<(), ::rs_ervice::RsServiceError> {
+                Ok(())
+            }
+        }
+    }
+}
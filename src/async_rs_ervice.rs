@@ -0,0 +1,1078 @@
+//-----------------------------------
+// Note: The async backend, compiled as `tokio_rs_ervice` or `async_std_rs_ervice`.
+// Locking and hooks are async; the few runtime calls (mutex, timeout, sleep) go through `async_runtime`.
+use std::{
+    any::{Any, TypeId}, collections::BTreeMap, future::{poll_fn, Future}, panic::{catch_unwind, AssertUnwindSafe}, pin::{pin, Pin}, task::Poll, time::Duration, sync::{Arc, OnceLock}
+};
+
+#[cfg(feature = "serde")]
+use crate::common::category_to_json;
+use crate::{async_runtime::{self, Mutex}, build_stream::{emit, BuildEvent, BuildEvents, BuildStream}, common::{panic_error, CategoryToJson, ContainerStruct, MapForContainer, MapForFunctions, RsServiceError, ServiceContainer, ServiceMetadata, SharedFn}, reentrancy, trace::traced, RSContext};
+pub type AsyncHooksResult = Result<(), RsServiceError>;
+pub trait RSContextService: Any + Send + Sync + 'static {
+    /// Error returned by the hooks. Anything convertible into `RsServiceError` works, so a service
+    /// can fail with its own domain error (given a `From` impl); the builder converts it.
+    /// Use `RsServiceError` itself when there is nothing to convert.
+    type Error: Into<RsServiceError>;
+
+    /// Called by the framework to get a new instance of the service.
+    /// Typically implemented by a procedural macro.
+    fn on_register_crate_instance() -> impl Future<Output=Self> where Self: Sized;
+
+    /// Called after the service instance is created and before it's wrapped
+    /// in `Arc<Mutex<T>>` and stored in the builder.
+    /// Ideal for initial setup that might need mutable access to self
+    /// or access to builder configurations.
+    /// Dependencies can be pulled in with `builder.request_register::<Dep>()`.
+    fn on_service_created(&mut self, builder: &mut RSContextBuilder) -> impl std::future::Future<Output = Result<(), Self::Error>>;
+
+    /// (Optional) Called after all services are built and the RSContext is ready.
+    /// This hook would be called on `&self` (obtained via MutexGuard).
+    /// Hooks run one at a time by descending `priority`, then in registration order,
+    /// only after every service has been created,
+    /// and only this service's lock is held meanwhile: other services can be locked through
+    /// `context`, but locking this service again from its own hook deadlocks.
+    /// The context is complete before the first hook runs: every registered service is callable
+    /// and listed by `context.get_metadata()`, so a service may enumerate its peers here.
+    /// A task spawned here that needs the context should take a clone of it; cloning is cheap
+    /// and `build` still returns the context.
+    fn on_all_services_built(&self, context: &RSContext) -> impl std::future::Future<Output = Result<(), Self::Error>>;
+
+    /// (Optional) Reports whether the service is healthy, e.g. for a readiness probe.
+    /// Called by `RSContext::check_all_health` with this service's lock held.
+    fn health_check(&self) -> impl std::future::Future<Output = Result<(), Self::Error>> {
+        async { Ok(()) }
+    }
+
+    /// (Optional) TypeIds of the services this service depends on.
+    /// The builder creates those services first, so `on_service_created` can rely on them;
+    /// `build` fails if one of them was never registered.
+    fn dependencies() -> Vec<TypeId> where Self: Sized {
+        Vec::new()
+    }
+
+    /// (Optional) TypeIds of services this service uses if they are registered, e.g. a metrics
+    /// exporter that enhances its peers. Unlike `dependencies`, `build` doesn't fail without them.
+    /// A peer that is registered but still waiting for its own dependencies is created first,
+    /// unless it waits for this service, in which case it is created after; check whether one exists with `call_pending` or `RSContext::contains`.
+    fn optional_dependencies() -> Vec<TypeId> where Self: Sized {
+        Vec::new()
+    }
+
+    /// (Optional) Orders `on_all_services_built` hooks: higher priorities run first.
+    /// Only that hook is reordered; `on_service_created` still runs as each service is registered,
+    /// after its `dependencies`. So a service with a higher priority than its dependency
+    /// has its `on_all_services_built` run first, even though it was created after the dependency.
+    fn priority() -> i32 where Self: Sized {
+        0
+    }
+
+    /// (Optional) TypeId of the category this service reads with `get_category` or `with_category_mut`.
+    /// `RSContextBuilder::validate` reports the service if the builder's category is of another type.
+    fn category_type() -> Option<TypeId> where Self: Sized {
+        None
+    }
+
+    /// (Optional) Logical name used in logs, spans and `ServiceMetadata::name`, instead of the
+    /// fully-qualified type path. `#[r_service(auto_impl)]` returns the `#[r_service_struct(name = "...")]` name.
+    fn name() -> &'static str where Self: Sized {
+        std::any::type_name::<Self>()
+    }
+}
+/// Storage for a service registered with `register_lazy`.
+/// The instance is created by the first `try_call::<T>()` (or an accessor built on it) and cached;
+/// if its `on_all_services_built` hook fails, the error is cached instead.
+pub(crate) struct LazyService<T> {
+    cell: OnceLock<Result<Arc<Mutex<T>>, RsServiceError>>,
+    /// Serializes the first creation so the constructor runs only once.
+    init: Mutex<()>,
+    /// Hook settings of the builder that registered the service, filled in by `build`.
+    settings: SharedHookSettings,
+}
+
+impl<T: RSContextService> LazyService<T> {
+    /// Returns the cached instance, or `NotFound` if it hasn't been created yet.
+    pub(crate) fn resolve(&self, _context: &RSContext) -> Result<Arc<Mutex<T>>, RsServiceError> {
+        self.cell.get().cloned()
+            .unwrap_or_else(|| Err(RsServiceError::NotFound(std::any::type_name::<T>().to_string())))
+    }
+
+    /// Returns the cached instance, creating it and running its `on_all_services_built` hook
+    /// the way `build` runs eager hooks on first use.
+    pub(crate) async fn resolve_async(&self, context: &RSContext) -> Result<Arc<Mutex<T>>, RsServiceError> {
+        if let Some(service) = self.cell.get() {
+            return service.clone();
+        }
+        let _init_guard = self.init.lock().await;
+        if let Some(service) = self.cell.get() {
+            return service.clone();
+        }
+        let settings = HookSettings::of(&self.settings);
+        let catch_panics = settings.catch_panics;
+        let built = async {
+            let instance = run_hook::<T, _>(catch_panics, "on_register_crate_instance", new_instance::<T>());
+            let service = Arc::new(Mutex::new(traced("on_register_crate_instance", T::name(), instance).await?));
+            let guard = service.lock().await;
+            let built = run_hook::<T, _>(catch_panics, "on_all_services_built", reentrancy::holding::<T, _>(guard.on_all_services_built(context)));
+            traced("on_all_services_built", T::name(), built).await?;
+            drop(guard);
+            Ok(service)
+        }.await;
+        self.cell.get_or_init(|| built.map_err(|e| settings.map_error(e))).clone()
+    }
+}
+
+/// Rewrites hook failures, e.g. to add a correlation id; set with `map_hook_errors`.
+type HookErrorMapper = Arc<dyn Fn(TypeId, RsServiceError) -> RsServiceError + Send + Sync>;
+
+/// The builder settings hooks run with after `build`: by lazy services, which are created on first use,
+/// and by `RSContext::check_all_health`.
+pub(crate) struct HookSettings {
+    catch_panics: bool,
+    hook_error_mapper: Option<HookErrorMapper>,
+}
+
+/// Settings of contexts made without a builder, e.g. by `RSContext::with_service`.
+static DEFAULT_HOOK_SETTINGS: HookSettings = HookSettings { catch_panics: true, hook_error_mapper: None };
+
+/// Builder settings shared by a builder, its lazy services and the context it builds, filled in by `build`.
+pub(crate) type SharedHookSettings = Arc<OnceLock<HookSettings>>;
+
+impl HookSettings {
+    /// The settings in `settings`, or the defaults if no builder filled them in.
+    pub(crate) fn of(settings: &OnceLock<HookSettings>) -> &HookSettings {
+        settings.get().unwrap_or(&DEFAULT_HOOK_SETTINGS)
+    }
+
+    /// Whether panics in hooks are returned as errors instead of unwinding.
+    pub(crate) fn catch_panics(&self) -> bool {
+        self.catch_panics
+    }
+
+    /// Passes a hook failure through the builder's hook error mapper, if one was set.
+    pub(crate) fn map_error(&self, error: RsServiceError) -> RsServiceError {
+        map_hook_error(&self.hook_error_mapper, error)
+    }
+}
+
+/// Passes a `HookFailed` error through the builder's hook error mapper, if one is set.
+fn map_hook_error(mapper: &Option<HookErrorMapper>, error: RsServiceError) -> RsServiceError {
+    match (mapper, &error) {
+        (Some(mapper), RsServiceError::HookFailed { type_id, .. }) => mapper(*type_id, error),
+        _ => error,
+    }
+}
+
+type FutureHookResult<'a> = Pin<Box<dyn Future<Output = AsyncHooksResult> + 'a>>;
+
+/// Runs a hook future of service T, wrapping its error in `HookFailed`.
+/// With `catch_panics`, a panic while polling the hook is returned as a `HookFailed` too.
+async fn run_hook<T: Any, R>(catch_panics: bool, hook: &'static str, future: impl Future<Output = Result<R, impl Into<RsServiceError>>>) -> Result<R, RsServiceError> {
+    let future = async { future.await.map_err(Into::into) };
+    let result = if catch_panics {
+        let mut future = pin!(future);
+        poll_fn(|cx| match catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx))) {
+            Ok(poll) => poll,
+            Err(payload) => Poll::Ready(Err(panic_error(payload))),
+        }).await
+    } else {
+        future.await
+    };
+    result.map_err(|e| RsServiceError::hook_failed::<T>(hook, e))
+}
+
+/// Runs `on_register_crate_instance` of T as an infallible hook future, for `run_hook`.
+async fn new_instance<T: RSContextService>() -> Result<T, RsServiceError> {
+    Ok(T::on_register_crate_instance().await)
+}
+
+/// Metadata of service T, named by `T::name()`.
+pub(crate) fn metadata_of<T: RSContextService>() -> ServiceMetadata {
+    ServiceMetadata::of::<T>().with_name(T::name())
+}
+
+/// Locks the service stored in `container` and runs its `health_check`, catching a panic in it
+/// if `catch_panics` is set. Services registered with `register_shared` are checked without a lock.
+/// Lazy services are checked once created, and report the failure of their `on_all_services_built` hook.
+pub(crate) fn check_health<T: RSContextService>(container: &ContainerStruct, catch_panics: bool) -> FutureHookResult<'_> {
+    Box::pin(async move {
+        if let Some(service) = container.downcast_ref::<Arc<T>>() {
+            return run_hook::<T, _>(catch_panics, "health_check", service.health_check()).await;
+        }
+        let service = match container.downcast_ref::<LazyService<T>>() {
+            Some(lazy) => match lazy.cell.get() {
+                Some(created) => created.as_ref().map_err(Clone::clone)?,
+                None => return Ok(()),
+            },
+            None => match container.downcast_ref::<Arc<Mutex<T>>>() {
+                Some(service) => service,
+                None => return Ok(()),
+            },
+        };
+        reentrancy::assert_not_held::<T>();
+        let guard = service.lock().await;
+        run_hook::<T, _>(catch_panics, "health_check", reentrancy::holding::<T, _>(guard.health_check())).await
+    })
+}
+
+/// Swaps a new instance into `service` and runs its `on_all_services_built` hook with the lock held,
+/// for `RSContext::reload`. Both hooks run with the context's builder settings, like lazy services.
+pub(crate) async fn reload_service<T: RSContextService>(service: &Mutex<T>, context: &RSContext, settings: &HookSettings) -> Result<(), RsServiceError> {
+    let catch_panics = settings.catch_panics;
+    let reloaded = async {
+        let instance = run_hook::<T, _>(catch_panics, "on_register_crate_instance", new_instance::<T>());
+        let instance = traced("on_register_crate_instance", T::name(), instance).await?;
+        reentrancy::assert_not_held::<T>();
+        let mut guard = service.lock().await;
+        *guard = instance;
+        let built = run_hook::<T, _>(catch_panics, "on_all_services_built", reentrancy::holding::<T, _>(guard.on_all_services_built(context)));
+        traced("on_all_services_built", T::name(), built).await
+    }.await;
+    reloaded.map_err(|e| settings.map_error(e))
+}
+/// Hooks borrow the built context, so they never keep it alive past `build()`.
+/// The flag is whether panics should be caught.
+type AfterAsyncBuildHook = Box<
+    dyn for<'a> Fn(&'a RSContext, bool) -> FutureHookResult<'a> + Send + Sync
+>;
+
+/// The after-build hooks of a built context, with the builder settings they run with.
+/// `build` runs them right away; `build_without_hooks` leaves them to `RSContext::run_post_build_hooks`.
+pub(crate) struct PostBuildHooks {
+    hooks: Vec<(i32, TypeId, AfterAsyncBuildHook)>,
+    catch_panics: bool,
+    hook_error_mapper: Option<HookErrorMapper>,
+    build_events: Option<BuildEvents>,
+}
+
+impl PostBuildHooks {
+    /// Runs the hooks against `context` in priority order, stopping at the first failure.
+    pub(crate) async fn run(self, context: &RSContext) -> Result<(), RsServiceError> {
+        // Every service exists by now; run the hooks one at a time so each holds only its own lock.
+        // Hooks only borrow the context, so it is returned as-is once they finish.
+        for (_, type_id, async_hook) in self.hooks {
+            emit(&self.build_events, BuildEvent::HookStarted(type_id));
+            async_hook(context, self.catch_panics).await
+                .map_err(|e| map_hook_error(&self.hook_error_mapper, e))?;
+            emit(&self.build_events, BuildEvent::HookFinished(type_id));
+        }
+        Ok(())
+    }
+}
+
+/// A boxed registration step, e.g. `Box::new(|builder| Box::pin(builder.register::<MyService>()))`,
+/// so modules can contribute services without the caller naming their types. See `extend`.
+pub type Registration = Box<
+    dyn FnOnce(RSContextBuilder) -> Pin<Box<dyn Future<Output = Result<RSContextBuilder, RsServiceError>>>>
+>;
+
+/// A registration run against the builder in place, so a failure leaves the builder usable.
+type RequestedRegistration = Box<
+    dyn for<'a> FnOnce(&'a mut RSContextBuilder) -> Pin<Box<dyn Future<Output = Result<(), RsServiceError>> + 'a>>
+>;
+
+/// A registration waiting for the services it depends on to be registered.
+struct DeferredRegistration {
+    metadata: ServiceMetadata,
+    dependencies: Vec<TypeId>,
+    register: RequestedRegistration,
+}
+
+/// RSContextBuilder: For registering and building the context in tokio or async-std
+pub struct RSContextBuilder {
+    pending_services: MapForContainer,
+    /// Stores async closures to run after RSContext is built, with the service's priority,
+    /// kept sorted by descending priority.
+    after_build_async_hooks: Vec<(i32, TypeId, AfterAsyncBuildHook)>,
+    category_info:Box<dyn Any + Send + Sync + 'static>,
+    /// Type name of `category_info`, shown by RSContext's `Debug`.
+    category_name: &'static str,
+    /// Serializer for `category_info`, set by `set_category_json`.
+    category_to_json: Option<CategoryToJson>,
+    /// Functions registered with `register_from_fn`.
+    functions: MapForFunctions,
+    /// Registrations requested by `on_service_created` hooks, run once the requesting service is stored.
+    requested_services: Vec<RequestedRegistration>,
+    /// Registrations whose dependencies are not registered yet.
+    deferred_services: Vec<DeferredRegistration>,
+    /// Type names of the services created so far, in creation order.
+    init_order: Vec<&'static str>,
+    /// Service maps of the parent contexts when building a child context.
+    parent_maps: Vec<Arc<MapForContainer>>,
+    /// Applied to every hook failure before it is returned.
+    hook_error_mapper: Option<HookErrorMapper>,
+    /// Whether panics in hooks are returned as errors instead of unwinding.
+    catch_panics: bool,
+    /// Whether `build` fails when no service was registered.
+    require_non_empty: bool,
+    /// Non-fatal startup concerns reported by hooks through `warn`.
+    warnings: Vec<String>,
+    /// Where `build` reports its progress when driven by `build_streaming`.
+    build_events: Option<BuildEvents>,
+    /// Type name and declared `category_type` of every registered service that declares one, for `validate`.
+    category_types: Vec<(&'static str, TypeId)>,
+    /// Shared with every lazy service, and filled in by `build` once the settings are final.
+    hook_settings: SharedHookSettings,
+}
+
+
+impl Default for RSContextBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// RSContextBuilder: For registering and building the context
+impl RSContextBuilder {
+    /// Creates a new RSContextBuilder instance.
+    pub fn new() -> Self {
+        RSContextBuilder {
+            pending_services: BTreeMap::new(),
+            init_order: Vec::new(),
+            after_build_async_hooks: Vec::new(),
+            category_info: Box::new(()), // Placeholder for category info, can be replaced with actual type
+            category_name: "()",
+            category_to_json: None,
+            functions: MapForFunctions::new(),
+            requested_services: Vec::new(),
+            deferred_services: Vec::new(),
+            parent_maps: Vec::new(),
+            hook_error_mapper: None,
+            catch_panics: true,
+            require_non_empty: false,
+            warnings: Vec::new(),
+            build_events: None,
+            category_types: Vec::new(),
+            hook_settings: Arc::new(OnceLock::new()),
+        }
+    }
+    /// Creates a new RSContextBuilder with room reserved for `service_count` services' hooks.
+    /// Useful when registering many services to avoid reallocating the hooks Vec.
+    pub fn with_service_hint(service_count: usize) -> Self {
+        let mut builder = Self::new();
+        builder.after_build_async_hooks.reserve(service_count);
+        builder
+    }
+    /// Creates a builder for a child context of the given parent service maps.
+    pub(crate) fn with_parent_maps(parent_maps: Vec<Arc<MapForContainer>>) -> Self {
+        let mut builder = Self::new();
+        builder.parent_maps = parent_maps;
+        builder
+    }
+    /// Whether a service type is registered, or waiting for its dependencies.
+    fn is_registered(&self, type_id: TypeId) -> bool {
+        self.pending_services.contains_key(&type_id)
+            || self.deferred_services.iter().any(|deferred| deferred.metadata.type_id == type_id)
+    }
+    /// Whether a dependency is available to the services created by this builder.
+    fn has_dependency(&self, type_id: TypeId) -> bool {
+        self.pending_services.contains_key(&type_id)
+            || self.parent_maps.iter().any(|parent| parent.contains_key(&type_id))
+    }
+    /// The services T waits for: `T::dependencies()`, plus its optional dependencies
+    /// that are registered but not created yet, unless they are themselves waiting for T.
+    fn dependencies_of<T: RSContextService>(&self) -> Vec<TypeId> {
+        let mut dependencies = T::dependencies();
+        dependencies.extend(T::optional_dependencies().into_iter().filter(|dependency| {
+            self.is_registered(*dependency)
+                && !self.has_dependency(*dependency)
+                && !self.waits_for(*dependency, TypeId::of::<T>())
+        }));
+        dependencies
+    }
+    /// Whether the deferred registration of `type_id` waits, directly or through other
+    /// deferred registrations, for `target`.
+    fn waits_for(&self, type_id: TypeId, target: TypeId) -> bool {
+        let mut stack = vec![type_id];
+        let mut visited = Vec::new();
+        while let Some(current) = stack.pop() {
+            if current == target {
+                return true;
+            }
+            if visited.contains(&current) {
+                continue;
+            }
+            visited.push(current);
+            if let Some(deferred) = self.deferred_services.iter().find(|deferred| deferred.metadata.type_id == current) {
+                stack.extend(deferred.dependencies.iter().copied());
+            }
+        }
+        false
+    }
+    /// Remembers the category type T declares, if any, so `validate` can compare it with the category.
+    fn record_category_type<T: RSContextService>(&mut self) {
+        if let Some(category_type) = T::category_type() {
+            self.category_types.push((T::name(), category_type));
+        }
+    }
+    /// Fails with `AlreadyRegistered` if T is registered, or waiting for its dependencies.
+    fn ensure_unregistered<T: ?Sized + 'static>(&self) -> Result<(),RsServiceError> {
+        if self.is_registered(TypeId::of::<T>()) {
+            return Err(RsServiceError::AlreadyRegistered(std::any::type_name::<T>().to_string()));
+        }
+        Ok(())
+    }
+    /// Stages the registration of service T: fails if T is already registered, defers `register`
+    /// until `dependencies_of::<T>()` are registered, or else runs it and the deferred registrations it unblocks.
+    async fn register_staged<T: RSContextService>(&mut self, register: RequestedRegistration) -> Result<(),RsServiceError> {
+        self.ensure_unregistered::<T>()?;
+        self.record_category_type::<T>();
+        let dependencies = self.dependencies_of::<T>();
+        if !dependencies.iter().all(|dependency| self.has_dependency(*dependency)) {
+            self.deferred_services.push(DeferredRegistration {
+                metadata: metadata_of::<T>(),
+                dependencies,
+                register,
+            });
+            return Ok(());
+        }
+        register(self).await?;
+        self.register_ready_deferred().await
+    }
+    /// Registers a service type T with the builder.
+    /// T must implement RSContextService, whose Send + Sync bound lets the context be shared across tasks.
+    /// If some of `T::dependencies()` are not registered yet, T is created once they are.
+    /// Each instantiation of a generic service (e.g. `Cache<String>` and `Cache<u64>`) is a distinct service.
+    pub async fn register<T>(mut self) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.try_register::<T>().await?;
+        Ok(self)
+    }
+    /// Registers service type T like `register`, but through `&mut self`, so an error
+    /// leaves the builder usable, e.g. to collect every failed registration before giving up.
+    /// Every service registered before the error, including T if it was created, stays registered.
+    pub async fn try_register<T>(&mut self) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_staged::<T>(Box::new(|builder: &mut RSContextBuilder| {
+            Box::pin(builder.create_service(async { Ok(T::on_register_crate_instance().await) }))
+        })).await
+    }
+    /// Registers service type T like `register`, but fails with `RsServiceError::Timeout` naming T
+    /// if `on_register_crate_instance` and `on_service_created` don't finish within `dur`,
+    /// e.g. when the hook calls out to the network. Services requested by T's hook are not timed.
+    pub async fn register_with_timeout<T>(mut self, dur: Duration) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_staged::<T>(Box::new(move |builder: &mut RSContextBuilder| {
+            Box::pin(builder.create_service_within::<T>(dur))
+        })).await?;
+        Ok(self)
+    }
+    /// Registers service type T like `register`, but retries `on_register_crate_instance` and
+    /// `on_service_created` up to `attempts` times (at least once) when the hook fails, e.g. while
+    /// a database is still starting. Waits `backoff` before the first retry, doubling it each time
+    /// (saturating at `Duration::MAX`). If every attempt fails, returns `RsServiceError::RetriesExhausted`
+    /// with the number of attempts, whose source is the last failure.
+    pub async fn register_with_retry<T>(mut self, attempts: u32, backoff: Duration) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_staged::<T>(Box::new(move |builder: &mut RSContextBuilder| {
+            Box::pin(builder.create_service_with_retry::<T>(attempts, backoff))
+        })).await?;
+        Ok(self)
+    }
+    /// Registers service type T, creating it with `factory` instead of `on_register_crate_instance`,
+    /// e.g. to inject a differently configured instance or a mock per build.
+    /// `on_service_created` still runs, and `factory` is called once `T::dependencies()` are registered.
+    pub async fn register_with<T>(self, factory: impl FnOnce() -> T + 'static) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_fallible(move || Ok(factory())).await
+    }
+    /// Registers service type T like `register_with`, with a `factory` that can fail,
+    /// e.g. when T is built from runtime config. The factory's error is returned
+    /// as `HookFailed` for T's `on_register_crate_instance` step, so it names the service.
+    pub async fn register_fallible<T>(mut self, factory: impl FnOnce() -> Result<T, RsServiceError> + 'static) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_staged::<T>(Box::new(move |builder: &mut RSContextBuilder| {
+            Box::pin(builder.create_service(async move { factory() }))
+        })).await?;
+        Ok(self)
+    }
+    /// Registers service type T stored as a plain `Arc<T>` instead of `Arc<Mutex<T>>`,
+    /// for services that never need `&mut self` once `on_service_created` has run
+    /// (e.g. a compiled regex registry), so callers share it without locking.
+    /// Retrieve it with `RSContext::call_shared`; `call` returns None for it.
+    pub async fn register_shared<T>(mut self) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_staged::<T>(Box::new(|builder: &mut RSContextBuilder| {
+            Box::pin(builder.create_shared_service::<T>())
+        })).await?;
+        Ok(self)
+    }
+    /// Registers an already constructed instance of service type T.
+    /// `on_register_crate_instance` is skipped, but `on_service_created` still runs on `value`,
+    /// and T still waits for `T::dependencies()` like `register` does.
+    pub async fn register_instance<T>(mut self, value: T) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_staged::<T>(Box::new(move |builder: &mut RSContextBuilder| {
+            Box::pin(builder.store_service(value))
+        })).await?;
+        Ok(self)
+    }
+    /// Applies every registration in order, stopping at the first error.
+    pub async fn extend(mut self, registrations: impl IntoIterator<Item = Registration>) -> Result<Self,RsServiceError> {
+        for registration in registrations {
+            self = registration(self).await?;
+        }
+        Ok(self)
+    }
+    /// Creates registrations that were waiting for dependencies which are now registered.
+    async fn register_ready_deferred(&mut self) -> Result<(),RsServiceError> {
+        while let Some(index) = self.deferred_services.iter().position(|deferred| {
+            deferred.dependencies.iter().all(|dependency| self.has_dependency(*dependency))
+        }) {
+            let deferred = self.deferred_services.remove(index);
+            (deferred.register)(self).await?;
+        }
+        Ok(())
+    }
+    /// Fails if `require_non_empty` is set and no service was registered.
+    fn check_non_empty(&self) -> Result<(), RsServiceError> {
+        if self.require_non_empty && self.pending_services.is_empty() {
+            return Err(RsServiceError::Other("no services registered".to_string()));
+        }
+        Ok(())
+    }
+    /// Fails with `MissingDependency` if a registration is still waiting for a dependency.
+    fn check_deferred(&self) -> Result<(), RsServiceError> {
+        match self.deferred_services.first() {
+            Some(deferred) => {
+                let dependency = deferred.dependencies.iter()
+                    .find(|dependency| !self.has_dependency(**dependency))
+                    .copied()
+                    .unwrap_or(deferred.metadata.type_id);
+                Err(RsServiceError::MissingDependency {
+                    service: deferred.metadata.type_name.to_string(),
+                    dependency,
+                })
+            }
+            None => Ok(()),
+        }
+    }
+    /// Type names of the services in the order the builder creates them: those already created,
+    /// then the deferred ones in the order their dependencies let `build` create them.
+    /// Deferred services whose dependencies are never registered, lazy services and trait objects
+    /// are left out, as are services a hook will request, since hooks haven't run yet.
+    pub fn resolution_order(&self) -> Vec<&'static str> {
+        let mut order = self.init_order.clone();
+        let mut available: Vec<TypeId> = Vec::new();
+        let mut unresolved: Vec<&DeferredRegistration> = self.deferred_services.iter().collect();
+        while let Some(index) = unresolved.iter().position(|deferred| {
+            deferred.dependencies.iter().all(|dependency| self.has_dependency(*dependency) || available.contains(dependency))
+        }) {
+            let deferred = unresolved.remove(index);
+            available.push(deferred.metadata.type_id);
+            order.push(deferred.metadata.type_name);
+        }
+        order
+    }
+    /// Checks the registrations for problems, without consuming the builder or running any hooks,
+    /// and reports them all at once: every unmet dependency, an empty builder under `require_non_empty`,
+    /// every service name (from `RSContextService::name`) shared by two service types,
+    /// and every service whose `RSContextService::category_type` isn't the type set with `set_category`.
+    pub fn validate(&self) -> Result<(), Vec<RsServiceError>> {
+        let mut errors = Vec::new();
+
+        // Resolve deferred registrations the same way the builder will, then report what is left.
+        let mut available: Vec<TypeId> = Vec::new();
+        let is_available = |available: &Vec<TypeId>, type_id: &TypeId| {
+            self.has_dependency(*type_id) || available.contains(type_id)
+        };
+        let mut unresolved: Vec<&DeferredRegistration> = self.deferred_services.iter().collect();
+        while let Some(index) = unresolved.iter().position(|deferred| {
+            deferred.dependencies.iter().all(|dependency| is_available(&available, dependency))
+        }) {
+            available.push(unresolved.remove(index).metadata.type_id);
+        }
+        for deferred in unresolved {
+            for dependency in &deferred.dependencies {
+                if !is_available(&available, dependency) {
+                    errors.push(RsServiceError::MissingDependency {
+                        service: deferred.metadata.type_name.to_string(),
+                        dependency: *dependency,
+                    });
+                }
+            }
+        }
+        errors.extend(self.check_non_empty().err());
+
+        // Service types are unique by TypeId, but their logical names may collide.
+        let mut names: Vec<(&'static str, &'static str)> = self.pending_services.values()
+            .map(|service| (service.metadata.name, service.metadata.type_name))
+            .chain(self.deferred_services.iter().map(|deferred| (deferred.metadata.name, deferred.metadata.type_name)))
+            .collect();
+        names.sort_unstable();
+        for pair in names.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                errors.push(RsServiceError::Other(format!(
+                    "service name {:?} is used by both {} and {}", pair[0].0, pair[0].1, pair[1].1
+                )));
+            }
+        }
+
+        let category = self.category_info.as_ref().type_id();
+        for (service, expected) in &self.category_types {
+            if *expected != category {
+                errors.push(RsServiceError::CategoryMismatch {
+                    service: service.to_string(),
+                    expected: *expected,
+                    found: self.category_name,
+                });
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+    /// Creates service T from the `instance` future, runs its `on_service_created` hook and stores it.
+    async fn create_service<T>(&mut self, instance: impl Future<Output = Result<T, RsServiceError>>) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = T::name();
+        traced("register", service, async {
+            let instance = self.create_instance(instance).await?;
+            self.store_service(instance).await
+        }).await
+    }
+    /// Creates service T and runs its `on_service_created` hook within `dur`, then stores it.
+    async fn create_service_within<T>(&mut self, dur: Duration) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = T::name();
+        let created = async {
+            let instance = self.create_instance(new_instance::<T>()).await?;
+            traced("register", service, self.insert_service(instance)).await
+        };
+        let created = async_runtime::timeout(dur, created)
+            .await
+            .unwrap_or_else(|| Err(RsServiceError::Timeout(format!("registering {service} took longer than {dur:?}"))));
+        // A timed-out hook may already have requested services; drop them like any other failure.
+        created.inspect_err(|_| self.requested_services.clear())?;
+        self.register_requested().await
+    }
+    /// Creates and stores service T, retrying with a doubling delay while its hooks fail.
+    async fn create_service_with_retry<T>(&mut self, attempts: u32, backoff: Duration) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = T::name();
+        let attempts = attempts.max(1);
+        let mut attempt = 1;
+        let mut delay = backoff;
+        loop {
+            let warnings = self.warnings.len();
+            let created = async {
+                let instance = self.create_instance(new_instance::<T>()).await?;
+                traced("register", service, self.insert_service(instance)).await
+            };
+            match created.await {
+                Ok(()) => return self.register_requested().await,
+                Err(e) if attempt == attempts => {
+                    self.requested_services.clear();
+                    self.warnings.truncate(warnings);
+                    return Err(RsServiceError::RetriesExhausted {
+                        service: service.to_string(),
+                        attempts,
+                        source: Arc::new(e),
+                    });
+                }
+                Err(_) => {
+                    // Drop what the failed hook requested and warned about, so the next attempt doesn't repeat it.
+                    self.requested_services.clear();
+                    self.warnings.truncate(warnings);
+                    async_runtime::sleep(delay).await;
+                    delay = delay.saturating_mul(2);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+    /// Creates service T, runs its `on_service_created` hook and stores it as a plain `Arc<T>`.
+    async fn create_shared_service<T>(&mut self) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = T::name();
+        traced("register", service, async {
+            let instance = self.create_instance(new_instance::<T>()).await?;
+            self.store_shared_service(instance).await
+        }).await
+    }
+    /// Creates an instance of service T from the `instance` future, run like a hook,
+    /// so its error or panic is returned as `HookFailed` naming T.
+    async fn create_instance<T>(&self, instance: impl Future<Output = Result<T, RsServiceError>>) -> Result<T, RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let created = run_hook::<T, _>(self.catch_panics, "on_register_crate_instance", instance);
+        traced("on_register_crate_instance", T::name(), created)
+            .await
+            .map_err(|e| map_hook_error(&self.hook_error_mapper, e))
+    }
+    /// Runs the `on_service_created` hook of an instance about to be stored.
+    async fn run_service_created<T>(&mut self, instance: &mut T) -> Result<(), RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let catch_panics = self.catch_panics;
+        let created = run_hook::<T, _>(catch_panics, "on_service_created", instance.on_service_created(self));
+        traced("on_service_created", T::name(), created)
+            .await
+            .map_err(
+                |e| map_hook_error(&self.hook_error_mapper, e)
+            )
+    }
+    /// Adds the after-build hook of service T, ordered by `T::priority()`.
+    fn push_after_build_hook<T>(&mut self, hook: AfterAsyncBuildHook)
+    where
+        T: RSContextService,
+    {
+        // Insert after every hook of equal or higher priority, keeping registration order among equals.
+        let priority = T::priority();
+        let at = self.after_build_async_hooks.partition_point(|(p, _, _)| *p >= priority);
+        self.after_build_async_hooks.insert(at, (priority, TypeId::of::<T>(), hook));
+    }
+    /// Registers the dependencies requested by a hook now that the requesting service is stored,
+    /// so a dependency requesting it back is skipped instead of recursing.
+    async fn register_requested(&mut self) -> Result<(),RsServiceError> {
+        for register_requested in std::mem::take(&mut self.requested_services) {
+            register_requested(self).await?;
+        }
+        Ok(())
+    }
+    /// Runs the `on_service_created` hook of an instance and stores it.
+    async fn store_service<T>(&mut self, instance: T) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        if let Err(e) = self.insert_service(instance).await {
+            // Drop what the failed hook requested, so the next registration doesn't pick it up.
+            self.requested_services.clear();
+            return Err(e);
+        }
+        self.register_requested().await
+    }
+    /// Runs the `on_service_created` hook of an instance and stores it with its after-build hook,
+    /// leaving the registrations the hook requested to `register_requested`.
+    async fn insert_service<T>(&mut self, mut instance: T) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let type_id = TypeId::of::<T>();
+        self.run_service_created(&mut instance).await?;
+
+        let service_arc_mutex: Arc<Mutex<T>> = Arc::new(Mutex::new(instance));
+
+        self.init_order.push(core::any::type_name::<T>());
+        self.pending_services.insert(
+            type_id,
+            ServiceContainer {
+                container: Box::new(service_arc_mutex.clone()) as ContainerStruct,
+                metadata: metadata_of::<T>(),
+                health_check: Some(check_health::<T>),
+            },
+        );
+
+        // Note: after_build_hooks must be async here
+        // You may want to define a separate Vec for async hooks, or use a feature flag.
+        // For demonstration, let's assume you add an `after_build_async_hooks` Vec:
+        {
+            let hook: AfterAsyncBuildHook = Box::new(move |ctx: &RSContext, catch_panics: bool| {
+                Box::pin(async move {
+                    // The service was stored above, but a lookup miss should fail the build, not panic.
+                    let arc_mutex = ctx.call::<T>()
+                        .ok_or_else(|| RsServiceError::NotFound(std::any::type_name::<T>().to_string()))?;
+                    let service_guard = arc_mutex.lock().await;
+                    let built = run_hook::<T, _>(catch_panics, "on_all_services_built", reentrancy::holding::<T, _>(service_guard.on_all_services_built(ctx)));
+                    traced("on_all_services_built", T::name(), built).await
+                }) as FutureHookResult<'_>
+            });
+            self.push_after_build_hook::<T>(hook);
+        }
+
+        Ok(())
+    }
+    /// Runs the `on_service_created` hook of an instance and stores it as a plain `Arc<T>`.
+    async fn store_shared_service<T>(&mut self, mut instance: T) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        if let Err(e) = self.run_service_created(&mut instance).await {
+            self.requested_services.clear();
+            return Err(e);
+        }
+        self.init_order.push(core::any::type_name::<T>());
+        self.pending_services.insert(
+            TypeId::of::<T>(),
+            ServiceContainer {
+                container: Box::new(Arc::new(instance)) as ContainerStruct,
+                metadata: metadata_of::<T>(),
+                health_check: Some(check_health::<T>),
+            },
+        );
+
+        let hook: AfterAsyncBuildHook = Box::new(move |ctx: &RSContext, catch_panics: bool| {
+            Box::pin(async move {
+                let service = ctx.call_shared::<T>()
+                    .ok_or_else(|| RsServiceError::NotFound(std::any::type_name::<T>().to_string()))?;
+                let built = run_hook::<T, _>(catch_panics, "on_all_services_built", service.on_all_services_built(ctx));
+                traced("on_all_services_built", T::name(), built).await
+            }) as FutureHookResult<'_>
+        });
+        self.push_after_build_hook::<T>(hook);
+
+        self.register_requested().await
+    }
+    /// Requests registration of service type T from inside an `on_service_created` hook.
+    /// T is registered right after the requesting service, unless it is already registered.
+    pub fn request_register<T>(&mut self)
+    where
+        T: RSContextService,
+    {
+        self.requested_services.push(Box::new(|builder: &mut RSContextBuilder| {
+            Box::pin(async move {
+                if builder.is_registered(TypeId::of::<T>()) {
+                    Ok(())
+                } else {
+                    builder.try_register::<T>().await
+                }
+            })
+        }));
+    }
+    /// Records a non-fatal startup concern, e.g. from `on_service_created` when a config value
+    /// falls back to a default. `build_with_warnings` returns them; `build` drops them.
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.warnings.push(message.into());
+    }
+    /// Retrieves a service that is already stored in this builder (or in a parent context),
+    /// so an `on_service_created` hook can wire in a peer registered before it.
+    /// Deferred and lazy services have no instance yet, so they are not returned.
+    pub fn call_pending<T>(&self) -> Option<Arc<Mutex<T>>>
+    where
+        T: RSContextService,
+    {
+        let type_id = TypeId::of::<T>();
+        self.pending_services
+            .get(&type_id)
+            .or_else(|| self.parent_maps.iter().find_map(|parent| parent.get(&type_id)))?
+            .container
+            .downcast_ref::<Arc<Mutex<T>>>()
+            .cloned()
+    }
+    /// Registers a trait object under the trait's own TypeId, e.g. `register_dyn::<dyn Greeter + Send>(Box::new(English))`,
+    /// so callers retrieve it with `RSContext::call_dyn` without naming the concrete type.
+    /// It is not an `RSContextService`, so no hooks run for it.
+    pub fn register_dyn<Dyn>(mut self, instance: Box<Dyn>) -> Result<Self,RsServiceError>
+    where
+        Dyn: ?Sized + Send + 'static,
+    {
+        self.ensure_unregistered::<Dyn>()?;
+        let type_id = TypeId::of::<Dyn>();
+        self.pending_services.insert(
+            type_id,
+            ServiceContainer {
+                container: Box::new(Arc::new(Mutex::new(instance))) as ContainerStruct,
+                metadata: ServiceMetadata::of::<Dyn>(),
+                health_check: None,
+            },
+        );
+        Ok(self)
+    }
+    /// Registers a service type T whose instance is created on the first `RSContext::try_call::<T>()`,
+    /// or the first async accessor (`call_lazy`, `call_map`, ...) that looks it up; the sync `call`
+    /// only returns it once created. `on_service_created` is not called for lazy services since the
+    /// builder no longer exists, and `on_all_services_built` runs once, when the instance is created,
+    /// with this builder's `catch_panics` and `map_hook_errors` settings. If it fails, `try_call` returns the error.
+    pub fn register_lazy<T>(mut self) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.ensure_unregistered::<T>()?;
+        self.record_category_type::<T>();
+        let type_id = TypeId::of::<T>();
+        let lazy_service: LazyService<T> = LazyService {
+            cell: OnceLock::new(),
+            init: Mutex::new(()),
+            settings: Arc::clone(&self.hook_settings),
+        };
+        self.pending_services.insert(
+            type_id,
+            ServiceContainer {
+                container: Box::new(lazy_service) as ContainerStruct,
+                metadata: metadata_of::<T>(),
+                health_check: Some(check_health::<T>),
+            },
+        );
+        Ok(self)
+    }
+    /// Sets a mapper that every hook failure (`RsServiceError::HookFailed`) passes through
+    /// before the builder returns it, along with the TypeId of the failing service.
+    /// Useful to annotate all hook errors in one place, e.g. with a correlation id.
+    pub fn map_hook_errors(mut self, f: impl Fn(TypeId, RsServiceError) -> RsServiceError + Send + Sync + 'static) -> Self {
+        self.hook_error_mapper = Some(Arc::new(f));
+        self
+    }
+    /// Makes `build` fail with `RsServiceError::Other("no services registered")` if nothing was registered,
+    /// which is almost always a configuration mistake. Off by default.
+    pub fn require_non_empty(mut self) -> Self {
+        self.require_non_empty = true;
+        self
+    }
+    /// Whether a panic in a hook (`on_register_crate_instance` or a `register_with` factory,
+    /// `on_service_created`, `on_all_services_built` or `health_check`) is caught and returned
+    /// as `RsServiceError::HookFailed` naming the service (the default), or left to unwind.
+    /// Set it before registering services, since services are created during registration.
+    pub fn catch_panics(mut self, catch: bool) -> Self {
+        self.catch_panics = catch;
+        self
+    }
+    /// Sets the category carried by the built context, readable with `RSContext::get_category`.
+    pub fn set_category<TC>(mut self, _category: TC) -> Result<Self, RsServiceError>
+    where
+        TC: Any + Send + Sync + 'static, // Ensure TC is a type that can be boxed
+    {
+        self.category_info = Box::new(_category);
+        self.category_name = std::any::type_name::<TC>();
+        self.category_to_json = None;
+        Ok(self)
+    }
+    /// Registers a plain function or closure under `name`, for behaviour that doesn't need
+    /// a service struct (e.g. a request handler). Retrieve it with `RSContext::call_fn::<A, R>(name)`.
+    /// Fails with `AlreadyRegistered` if a function with that name is registered already.
+    pub fn register_from_fn<A, R, F>(mut self, name: &str, f: F) -> Result<Self, RsServiceError>
+    where
+        A: 'static,
+        R: 'static,
+        F: Fn(A) -> R + Send + Sync + 'static,
+    {
+        if self.functions.contains_key(name) {
+            return Err(RsServiceError::AlreadyRegistered(name.to_string()));
+        }
+        let function: SharedFn<A, R> = Arc::new(f);
+        self.functions.insert(name.to_string(), Box::new(function));
+        Ok(self)
+    }
+    /// Runs `f` on the category if it is a `TC`, e.g. so services can add to a shared config
+    /// from `on_service_created`. Returns what `f` returns, or None if the category isn't a `TC`.
+    pub fn with_category_mut<TC, R>(&mut self, f: impl FnOnce(&mut TC) -> R) -> Option<R>
+    where
+        TC: Any,
+    {
+        self.category_info.downcast_mut::<TC>().map(f)
+    }
+    /// Sets the category by deserializing `json` into `TC`, so `RSContext::category_to_json`
+    /// can serialize it back later. Fails with `RsServiceError::Other` if `json` isn't a valid `TC`.
+    #[cfg(feature = "serde")]
+    pub fn set_category_json<TC>(self, json: &str) -> Result<Self, RsServiceError>
+    where
+        TC: serde::Serialize + serde::de::DeserializeOwned + Any + Send + Sync + 'static,
+    {
+        let category: TC = serde_json::from_str(json)
+            .map_err(|e| RsServiceError::Other(format!("invalid category JSON: {e}")))?;
+        let mut builder = self.set_category(category)?;
+        builder.category_to_json = Some(category_to_json::<TC>);
+        Ok(builder)
+    }
+    /// Creates the deferred services and the context, returning the after-build hooks unrun.
+    async fn into_parts(mut self) -> Result<(RSContext, PostBuildHooks), RsServiceError> {
+        // Create the deferred services whose dependencies were registered lazily.
+        self.register_ready_deferred().await?;
+        let builder = self;
+        builder.check_deferred()?;
+        builder.check_non_empty()?;
+        for type_id in builder.pending_services.keys() {
+            emit(&builder.build_events, BuildEvent::Registered(*type_id));
+        }
+        let context = RSContext {
+            service_map: Arc::new(builder.pending_services),
+            parent_maps: builder.parent_maps,
+            category: Arc::from(builder.category_info),
+            category_name: builder.category_name,
+            category_to_json: builder.category_to_json,
+            functions: Arc::new(builder.functions),
+            post_build_hooks: Arc::new(Mutex::new(None)),
+            hook_settings: Arc::clone(&builder.hook_settings),
+        };
+        let _ = builder.hook_settings.set(HookSettings {
+            catch_panics: builder.catch_panics,
+            hook_error_mapper: builder.hook_error_mapper.clone(),
+        });
+        let hooks = PostBuildHooks {
+            hooks: builder.after_build_async_hooks,
+            catch_panics: builder.catch_panics,
+            hook_error_mapper: builder.hook_error_mapper,
+            build_events: builder.build_events,
+        };
+        Ok((context, hooks))
+    }
+    /// Builds the RSContext from the registered services
+    /// and calls the on_all_services_built hooks.
+    pub async fn build(self) -> Result<RSContext, RsServiceError> { // Return Result for better error handling
+        traced("build", std::any::type_name::<RSContext>(), async move {
+            let (context, hooks) = self.into_parts().await?;
+            hooks.run(&context).await?;
+            Ok(context)
+        }).await
+    }
+    /// Builds the RSContext like `build`, but leaves the `on_all_services_built` hooks
+    /// to `RSContext::run_post_build_hooks`, so the context can be inspected first.
+    /// Services are usable meanwhile, but haven't seen their `on_all_services_built` yet.
+    pub async fn build_without_hooks(self) -> Result<RSContext, RsServiceError> {
+        traced("build", std::any::type_name::<RSContext>(), async move {
+            let (mut context, hooks) = self.into_parts().await?;
+            context.post_build_hooks = Arc::new(Mutex::new(Some(hooks)));
+            Ok(context)
+        }).await
+    }
+    /// Builds the RSContext like `build`, but fails with `RsServiceError::Timeout` if the deferred
+    /// registrations and after-build hooks don't finish within `dur`, so a hook stuck on a network
+    /// resource can't hang startup forever.
+    pub async fn build_with_timeout(self, dur: Duration) -> Result<RSContext, RsServiceError> {
+        async_runtime::timeout(dur, self.build())
+            .await
+            .ok_or_else(|| RsServiceError::Timeout(format!("building the context took longer than {dur:?}")))?
+    }
+    /// Builds the RSContext like `build`, reporting progress as a stream of `BuildEvent`s,
+    /// e.g. for a CLI showing "Initializing ServiceX..." during a slow startup.
+    /// The build runs as the stream is polled, and the last event, `Built`, carries its result.
+    pub fn build_streaming(mut self) -> BuildStream {
+        let events = BuildEvents::default();
+        self.build_events = Some(Arc::clone(&events));
+        BuildStream::new(Box::pin(self.build()), events)
+    }
+    /// Builds the RSContext like `build`, also returning the warnings hooks reported with `warn`
+    /// during registration, e.g. to log them once startup has finished.
+    pub async fn build_with_warnings(self) -> Result<(RSContext, Vec<String>), RsServiceError> {
+        // Deferred services are created first so the warnings of their hooks are included.
+        let mut builder = self;
+        builder.register_ready_deferred().await?;
+        let warnings = std::mem::take(&mut builder.warnings);
+        let context = builder.build().await?;
+        Ok((context, warnings))
+    }
+    /// Builds the RSContext behind an Arc, ready to be shared with spawned tasks.
+    /// The after-build hooks run against the same context that is returned.
+    pub async fn build_arc(self) -> Result<Arc<RSContext>, RsServiceError> {
+        traced("build", std::any::type_name::<RSContext>(), async move {
+            let (context, hooks) = self.into_parts().await?;
+            let context = Arc::new(context);
+            hooks.run(&context).await?;
+            Ok(context)
+        }).await
+    }
+}
\ No newline at end of file
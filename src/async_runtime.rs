@@ -0,0 +1,25 @@
+// The runtime calls the async backend makes, behind one shim so `async_rs_ervice.rs` serves
+// both tokio and async-std. Everything else in the backend is runtime-agnostic.
+
+use std::{future::Future, time::Duration};
+
+#[cfg(feature = "tokio")]
+pub(crate) use tokio::sync::Mutex;
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+pub(crate) use async_std::sync::Mutex;
+
+/// Awaits `future`, or returns None once `dur` has elapsed.
+pub(crate) async fn timeout<F: Future>(dur: Duration, future: F) -> Option<F::Output> {
+    #[cfg(feature = "tokio")]
+    return tokio::time::timeout(dur, future).await.ok();
+    #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+    return async_std::future::timeout(dur, future).await.ok();
+}
+
+/// Waits for `dur` without blocking the executor.
+pub(crate) async fn sleep(dur: Duration) {
+    #[cfg(feature = "tokio")]
+    tokio::time::sleep(dur).await;
+    #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+    async_std::task::sleep(dur).await;
+}
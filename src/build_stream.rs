@@ -0,0 +1,71 @@
+// Build progress as a `Stream`, for `RSContextBuilder::build_streaming`.
+// `build` pushes events into a queue shared with the stream; polling the stream drives the build
+// and hands out the queued events, ending with `Built`.
+
+use std::{any::TypeId, collections::VecDeque, future::Future, pin::Pin, sync::{Arc, Mutex, PoisonError}, task::{Context, Poll}};
+use futures_core::Stream;
+use crate::{RSContext, RsServiceError};
+
+/// A step of the build observed through `RSContextBuilder::build_streaming`.
+#[derive(Debug)]
+pub enum BuildEvent {
+    /// The service was created and is part of the context being built.
+    Registered(TypeId),
+    /// The service's `on_all_services_built` hook started.
+    HookStarted(TypeId),
+    /// The service's `on_all_services_built` hook succeeded.
+    HookFinished(TypeId),
+    /// The build finished, successfully or not; always the last event.
+    Built(Result<RSContext, RsServiceError>),
+}
+
+/// Queue `build` pushes events into while a `BuildStream` drives it.
+pub(crate) type BuildEvents = Arc<Mutex<VecDeque<BuildEvent>>>;
+
+/// Queues an event if the build is being streamed.
+pub(crate) fn emit(events: &Option<BuildEvents>, event: BuildEvent) {
+    if let Some(events) = events {
+        events.lock().unwrap_or_else(PoisonError::into_inner).push_back(event);
+    }
+}
+
+/// The `build` future a `BuildStream` drives.
+type BuildFuture = Pin<Box<dyn Future<Output = Result<RSContext, RsServiceError>>>>;
+
+/// Stream of `BuildEvent`s returned by `build_streaming`. Nothing is built until it is polled.
+pub struct BuildStream {
+    build: Option<BuildFuture>,
+    events: BuildEvents,
+}
+
+impl BuildStream {
+    pub(crate) fn new(build: BuildFuture, events: BuildEvents) -> Self {
+        BuildStream { build: Some(build), events }
+    }
+
+    fn next_queued(&self) -> Option<BuildEvent> {
+        self.events.lock().unwrap_or_else(PoisonError::into_inner).pop_front()
+    }
+}
+
+impl Stream for BuildStream {
+    type Item = BuildEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<BuildEvent>> {
+        if let Some(event) = self.next_queued() {
+            return Poll::Ready(Some(event));
+        }
+        let Some(build) = self.build.as_mut() else {
+            return Poll::Ready(None);
+        };
+        if let Poll::Ready(result) = build.as_mut().poll(cx) {
+            self.build = None;
+            emit(&Some(Arc::clone(&self.events)), BuildEvent::Built(result));
+        }
+        // Events queued during the poll are handed out first; otherwise the build registered the waker.
+        match self.next_queued() {
+            Some(event) => Poll::Ready(Some(event)),
+            None => Poll::Pending,
+        }
+    }
+}
@@ -1,17 +1,191 @@
-use std::{any::{Any, TypeId}, collections::BTreeMap, fmt}; // For custom error
-#[derive(Debug)]
-pub struct RsServiceError(
-    pub String
-);
-impl std::error::Error for RsServiceError {
-
+use core::{any::{Any, TypeId}, error::Error, fmt}; // For custom error
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+#[cfg(feature = "spin")]
+use alloc::{boxed::Box, string::{String, ToString}};
+/// Cloneable, so a single startup failure can be handed to several waiters.
+#[derive(Debug, Clone)]
+pub enum RsServiceError {
+    /// The service type was registered twice in the same builder.
+    AlreadyRegistered(String),
+    /// A lifecycle hook of the service returned an error.
+    HookFailed {
+        /// Type name of the service whose hook failed.
+        service: String,
+        /// TypeId of the service whose hook failed, for grouping failures by service.
+        type_id: TypeId,
+        hook: &'static str,
+        /// Shared rather than boxed so the error stays cloneable.
+        source: Arc<dyn Error + Send + Sync>,
+    },
+    /// The service is not registered in the context.
+    NotFound(String),
+    /// A service declared a dependency that was never registered.
+    MissingDependency {
+        service: String,
+        dependency: TypeId,
+    },
+    /// A service declared a `category_type` other than the type of the builder's category.
+    CategoryMismatch {
+        service: String,
+        expected: TypeId,
+        /// Type name of the category that was set.
+        found: &'static str,
+    },
+    /// The mutex guarding the service was poisoned.
+    LockPoisoned(String),
+    /// An operation did not finish within its time limit.
+    Timeout(String),
+    /// `register_with_retry` gave up on the service after `attempts` failed attempts.
+    RetriesExhausted {
+        service: String,
+        attempts: u32,
+        /// The error of the last attempt.
+        source: Arc<RsServiceError>,
+    },
+    /// An error from outside the crate, converted with `?`.
+    External {
+        /// What kind of error it is, e.g. "I/O"; shown before the message.
+        kind: &'static str,
+        /// Shared rather than boxed so the error stays cloneable.
+        source: Arc<dyn Error + Send + Sync>,
+    },
+    /// Any other failure.
+    Other(String),
+}
+impl Error for RsServiceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RsServiceError::HookFailed { source, .. } => Some(source.as_ref()),
+            RsServiceError::RetriesExhausted { source, .. } => Some(source.as_ref()),
+            RsServiceError::External { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
 }
 impl fmt::Display for RsServiceError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "RsService Error: {}", self.0)
+        match self {
+            RsServiceError::AlreadyRegistered(service) => write!(f, "RsService Error: Service type {:?} already registered.", service),
+            RsServiceError::HookFailed { service, hook, source, .. } => write!(f, "RsService Error: {} hook failed for {}: {}", hook, service, source),
+            RsServiceError::NotFound(service) => write!(f, "RsService Error: Service type {:?} not found.", service),
+            RsServiceError::MissingDependency { service, dependency } => write!(f, "RsService Error: Service type {:?} depends on {:?}, which was never created (missing or circular dependency).", service, dependency),
+            RsServiceError::CategoryMismatch { service, expected, found } => write!(f, "RsService Error: Service type {:?} expects a category of type {:?}, but the category is {}.", service, expected, found),
+            RsServiceError::LockPoisoned(service) => write!(f, "RsService Error: Mutex poisoned for {}", service),
+            RsServiceError::Timeout(operation) => write!(f, "RsService Error: Timed out: {}", operation),
+            RsServiceError::RetriesExhausted { service, attempts, source } => write!(f, "RsService Error: Gave up on {} after {} attempts: {}", service, attempts, source),
+            RsServiceError::External { kind, source } => write!(f, "RsService Error: {} error: {}", kind, source),
+            RsServiceError::Other(message) => write!(f, "RsService Error: {}", message),
+        }
+    }
+}
+
+#[cfg(not(feature = "spin"))]
+impl From<std::io::Error> for RsServiceError {
+    fn from(e: std::io::Error) -> Self {
+        RsServiceError::External { kind: "I/O", source: Arc::new(e) }
+    }
+}
+
+#[cfg(not(feature = "spin"))]
+impl From<Box<dyn Error + Send + Sync>> for RsServiceError {
+    fn from(e: Box<dyn Error + Send + Sync>) -> Self {
+        RsServiceError::External { kind: "external", source: Arc::from(e) }
+    }
+}
+
+#[cfg(not(feature = "spin"))]
+impl From<Box<dyn Error>> for RsServiceError {
+    /// The error isn't `Send + Sync`, so only its message is kept;
+    /// box it as `Box<dyn Error + Send + Sync>` to keep it as the source.
+    fn from(e: Box<dyn Error>) -> Self {
+        RsServiceError::Other(e.to_string())
     }
 }
 
-pub type ContainerStruct = Box<dyn Any>;
-pub type MapForContainer = BTreeMap<TypeId, ContainerStruct>;
-pub type CategoryType = Box<dyn Any + Send + Sync>;
\ No newline at end of file
+#[cfg(not(feature = "spin"))]
+impl<T> From<std::sync::PoisonError<T>> for RsServiceError {
+    /// Names the poisoned guard type, e.g. `MutexGuard<MyService>`.
+    fn from(_: std::sync::PoisonError<T>) -> Self {
+        RsServiceError::LockPoisoned(core::any::type_name::<T>().to_string())
+    }
+}
+
+impl RsServiceError {
+    /// Wraps an error returned by the `hook` lifecycle hook of service T.
+    pub fn hook_failed<T: Any>(hook: &'static str, source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        RsServiceError::HookFailed {
+            service: core::any::type_name::<T>().to_string(),
+            type_id: TypeId::of::<T>(),
+            hook,
+            source: Arc::from(source.into()),
+        }
+    }
+}
+
+/// Runtime information about a service stored in a built RSContext.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceMetadata {
+    pub type_id: TypeId,
+    pub type_name: &'static str,
+    /// Logical name from `RSContextService::name`, for logs; the type name unless overridden.
+    pub name: &'static str,
+}
+
+/// Turns the payload of a panic caught in a hook into an error carrying the panic message.
+/// Only the backends' `run_hook` calls it, and wraps the result in `HookFailed` naming the service.
+#[cfg(not(feature = "spin"))]
+pub(crate) fn panic_error(payload: Box<dyn Any + Send>) -> RsServiceError {
+    let message = payload.downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string());
+    RsServiceError::Other(format!("hook panicked: {message}"))
+}
+
+impl ServiceMetadata {
+    pub fn of<T: Any + ?Sized>() -> Self {
+        ServiceMetadata {
+            type_id: TypeId::of::<T>(),
+            type_name: core::any::type_name::<T>(),
+            name: core::any::type_name::<T>(),
+        }
+    }
+
+    /// Replaces the logical name, e.g. with `RSContextService::name`.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+}
+
+/// A type-erased service together with its metadata.
+pub struct ServiceContainer {
+    /// The service, type-erased as `Box<dyn Any + Send + Sync>`. It holds an `Arc<Mutex<T>>` for
+    /// mutex-guarded services, an `Arc<T>` for `register_shared`, an `Arc<Mutex<Box<Dyn>>>` for
+    /// `register_dyn::<Dyn>` and a crate-private lazy slot for `register_lazy`.
+    pub container: ContainerStruct,
+    pub metadata: ServiceMetadata,
+    /// Runs the service's `health_check` on `container`; None for trait objects.
+    pub health_check: Option<HealthCheck>,
+}
+
+pub type ContainerStruct = Box<dyn Any + Send + Sync>;
+pub type MapForContainer = BTreeMap<TypeId, ServiceContainer>;
+pub type CategoryType = Arc<dyn Any + Send + Sync>;
+/// A function registered with `register_from_fn`, shared by everyone who retrieves it.
+pub type SharedFn<A, R> = Arc<dyn Fn(A) -> R + Send + Sync>;
+/// Functions registered with `register_from_fn` by name, each a type-erased `SharedFn`.
+pub type MapForFunctions = BTreeMap<String, ContainerStruct>;
+/// Serializes a category back to JSON; recorded by `set_category_json`.
+pub type CategoryToJson = fn(&(dyn Any + Send + Sync)) -> Option<String>;
+
+/// Serializes the category if it is of type `TC`.
+#[cfg(feature = "serde")]
+pub(crate) fn category_to_json<TC: serde::Serialize + 'static>(category: &(dyn Any + Send + Sync)) -> Option<String> {
+    serde_json::to_string(category.downcast_ref::<TC>()?).ok()
+}
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+pub type HealthCheck = fn(&ContainerStruct, bool) -> Result<(), RsServiceError>;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub type HealthCheck = for<'a> fn(&'a ContainerStruct, bool) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), RsServiceError>> + 'a>>;
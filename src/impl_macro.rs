@@ -0,0 +1,72 @@
+// `impl_rs_service!`: writes a service's hooks once for every backend.
+// The hooks are written as plain functions; the vanilla backend implements them as they are,
+// while tokio and async-std wrap each one in an `async fn` and use `RsServiceError` as `Error`.
+// The macro is defined per backend here, so the feature check happens in this crate, not the caller's.
+// `health_check` and the no-argument items (`dependencies`, `priority`, `name`, ..) may follow the
+// three hooks; `health_check` becomes async like the hooks, the others are copied as they are.
+// Hook bodies can't `.await`; services that need async hooks implement the trait directly.
+
+/// Implements `RSContextService` from its three hooks written once as plain functions
+/// inside `impl MyService { .. }`, so the same service file compiles on every backend.
+/// They may be followed by `health_check` and by overrides of `dependencies`, `priority`, etc.
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[macro_export]
+macro_rules! impl_rs_service {
+    (
+        impl $service:ty {
+            fn on_register_crate_instance() -> Self $create:block
+            fn on_service_created(&mut $created_self:ident, $builder:ident: $builder_ty:ty) -> $created_ret:ty $created:block
+            fn on_all_services_built(&$built_self:ident, $context:ident: $context_ty:ty) -> $built_ret:ty $built:block
+            $($items:tt)*
+        }
+    ) => {
+        impl $crate::RSContextService for $service {
+            fn on_register_crate_instance() -> Self $create
+            fn on_service_created(&mut $created_self, $builder: $builder_ty) -> $created_ret $created
+            fn on_all_services_built(&$built_self, $context: $context_ty) -> $built_ret $built
+            $crate::impl_rs_service!(@items $($items)*);
+        }
+    };
+    (@items) => {};
+    (@items fn health_check(&$health_self:ident) -> $health_ret:ty $health:block $($items:tt)*) => {
+        fn health_check(&$health_self) -> $health_ret $health
+        $crate::impl_rs_service!(@items $($items)*);
+    };
+    (@items fn $item:ident() -> $item_ret:ty $item_body:block $($items:tt)*) => {
+        fn $item() -> $item_ret $item_body
+        $crate::impl_rs_service!(@items $($items)*);
+    };
+}
+
+/// Implements `RSContextService` from its three hooks written once as plain functions
+/// inside `impl MyService { .. }`, so the same service file compiles on every backend.
+/// They may be followed by `health_check` and by overrides of `dependencies`, `priority`, etc.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[macro_export]
+macro_rules! impl_rs_service {
+    (
+        impl $service:ty {
+            fn on_register_crate_instance() -> Self $create:block
+            fn on_service_created(&mut $created_self:ident, $builder:ident: $builder_ty:ty) -> $created_ret:ty $created:block
+            fn on_all_services_built(&$built_self:ident, $context:ident: $context_ty:ty) -> $built_ret:ty $built:block
+            $($items:tt)*
+        }
+    ) => {
+        impl $crate::RSContextService for $service {
+            type Error = $crate::RsServiceError;
+            async fn on_register_crate_instance() -> Self $create
+            async fn on_service_created(&mut $created_self, $builder: $builder_ty) -> $created_ret $created
+            async fn on_all_services_built(&$built_self, $context: $context_ty) -> $built_ret $built
+            $crate::impl_rs_service!(@items $($items)*);
+        }
+    };
+    (@items) => {};
+    (@items fn health_check(&$health_self:ident) -> $health_ret:ty $health:block $($items:tt)*) => {
+        async fn health_check(&$health_self) -> $health_ret $health
+        $crate::impl_rs_service!(@items $($items)*);
+    };
+    (@items fn $item:ident() -> $item_ret:ty $item_body:block $($items:tt)*) => {
+        fn $item() -> $item_ret $item_body
+        $crate::impl_rs_service!(@items $($items)*);
+    };
+}
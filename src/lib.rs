@@ -1,43 +1,748 @@
-use std::{any::TypeId, sync::{Arc}};
+#![cfg_attr(feature = "spin", no_std)]
+extern crate alloc;
 
-use common::{CategoryType, MapForContainer};
+use core::any::{Any, TypeId};
+use alloc::sync::{Arc, Weak};
+#[cfg(feature = "spin")]
+use alloc::{boxed::Box, string::ToString, vec, vec::Vec};
+#[cfg(all(feature = "spin", feature = "serde"))]
+use alloc::string::String;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use std::time::Duration;
+
+use common::{CategoryToJson, CategoryType, ContainerStruct, MapForContainer, MapForFunctions, ServiceContainer, ServiceMetadata, SharedFn};
 
 pub mod common;
+pub mod prelude;
+mod impl_macro;
+#[cfg(feature = "test-util")]
+mod test_util;
+#[cfg(feature = "test-util")]
+pub use test_util::TestContextBuilder;
+mod trace;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod reentrancy;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod build_stream;
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub use build_stream::{BuildEvent, BuildStream};
+pub use common::RsServiceError;
+
+// Used by `#[r_service(auto_impl)]`: an inherent `RS_SERVICE_NAME` from `#[r_service_struct]` shadows this
+// blanket default, so `name()` can tell whether the struct macro was applied.
+// `Vec` and `vec!` are re-exported so the generated code also compiles in `#![no_std]` crates.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+    pub trait ServiceNameFallback {
+        const RS_SERVICE_NAME: &'static str = "";
+    }
+    impl<T: ?Sized> ServiceNameFallback for T {}
+}
+
+#[cfg(all(feature = "tokio", feature = "async-std"))]
+compile_error!("features `tokio` and `async-std` select different backends; enable only one of them");
+#[cfg(all(feature = "spin", any(feature = "tokio", feature = "async-std")))]
+compile_error!("feature `spin` is for the no_std vanilla backend and can't be combined with `tokio` or `async-std`");
+#[cfg(all(feature = "spin", feature = "tracing"))]
+compile_error!("feature `tracing` measures elapsed time with std and can't be combined with `spin`");
+#[cfg(all(feature = "parking_lot", any(feature = "tokio", feature = "async-std")))]
+compile_error!("feature `parking_lot` swaps the vanilla backend's mutex and can't be combined with `tokio` or `async-std`");
+#[cfg(all(feature = "parking_lot", feature = "spin"))]
+compile_error!("features `parking_lot` and `spin` select different mutexes; enable only one of them");
 
-#[cfg(not(feature = "tokio"))]
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
 pub mod vanilla_rs_ervice;
-#[cfg(not(feature = "tokio"))]
-use vanilla_rs_ervice::RSContextService;
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+pub use vanilla_rs_ervice::{RSContextBuilder, RSContextService, Registration, ServiceMutex};
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+use vanilla_rs_ervice::{HookSettings, LazyService, PostBuildHooks, SharedHookSettings, check_health, lock_service, metadata_of, reload_service, ServiceMutex as Mutex};
+
+// tokio and async-std share one backend source; only `async_runtime` differs between them.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+mod async_runtime;
+#[cfg(feature = "tokio")]
+#[path = "async_rs_ervice.rs"]
+pub mod tokio_rs_ervice;
+#[cfg(feature = "tokio")]
+pub use tokio_rs_ervice::{RSContextBuilder, RSContextService, Registration};
+#[cfg(feature = "tokio")]
+use tokio_rs_ervice::{HookSettings, LazyService, PostBuildHooks, SharedHookSettings, check_health, metadata_of, reload_service};
+
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+#[path = "async_rs_ervice.rs"]
+pub mod async_std_rs_ervice;
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+pub use async_std_rs_ervice::{RSContextBuilder, RSContextService, Registration};
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+use async_std_rs_ervice::{HookSettings, LazyService, PostBuildHooks, SharedHookSettings, check_health, metadata_of, reload_service};
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+use async_runtime::Mutex;
+
+/// A registered service as handed out by `RSContext::call`.
+type SharedService<T> = Arc<Mutex<T>>;
 
 // --- Core Service Trait ---
-/// RSContextService: Trait for services that can be registered in RSContext.
-/// This trait defines the lifecycle hooks for services in the context.
-/// It will be managed by the RSContextBuilder.
+// RSContextService: Trait for services that can be registered in RSContext.
+// This trait defines the lifecycle hooks for services in the context.
+// It will be managed by the RSContextBuilder.
 
 
 // --- RSContext: Holds and provides access to services ---
 /// The main context for managing registered services.
 /// It provides methods to retrieve service instances.
+/// Cloning is cheap and shallow: clones share the same service instances, category and functions,
+/// so a service swapped with `replace_service` or `reload` is seen through every clone.
+#[derive(Clone)]
 pub struct RSContext where
     {
-        /// Stores Box<Arc<Mutex<T>>> type-erased as Box<dyn Any + ...>
-    service_map: MapForContainer,
+        /// Stores each service type-erased with its metadata; see `ServiceContainer::container` for the shapes.
+    service_map: Arc<MapForContainer>,
+    /// Service maps of the parent contexts (nearest first), searched when a type isn't in `service_map`.
+    parent_maps: Vec<Arc<MapForContainer>>,
     category: CategoryType,
+    /// Type name of `category`, shown by `Debug`.
+    category_name: &'static str,
+    /// Serializer recorded by `set_category_json`, if the category was set that way.
+    category_to_json: Option<CategoryToJson>,
+    /// Functions registered with `register_from_fn`.
+    functions: Arc<MapForFunctions>,
+    /// After-build hooks held back by `build_without_hooks`, taken by `run_post_build_hooks`.
+    post_build_hooks: Arc<Mutex<Option<PostBuildHooks>>>,
+    /// Settings of the builder, for the hooks run after `build` such as `health_check`.
+    hook_settings: SharedHookSettings,
+}
+
+impl Default for RSContext {
+    /// An empty context with the `()` category.
+    fn default() -> Self {
+        RSContext {
+            service_map: Arc::new(MapForContainer::new()),
+            parent_maps: Vec::new(),
+            category: Arc::new(()),
+            category_name: "()",
+            category_to_json: None,
+            functions: Arc::new(MapForFunctions::new()),
+            post_build_hooks: Arc::new(Mutex::new(None)),
+            hook_settings: SharedHookSettings::default(),
+        }
+    }
+}
+
+impl core::fmt::Debug for RSContext {
+    /// Lists the type names of the services held by the context and of its category;
+    /// the type-erased instances themselves are not printed.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RSContext")
+            .field("services", &self.service_type_names())
+            .field("functions", &self.functions.keys().collect::<Vec<_>>())
+            .field("category", &self.category_name)
+            .field("parents", &self.parent_maps.len())
+            .finish()
+    }
+}
+
+impl IntoIterator for &RSContext {
+    type Item = ServiceMetadata;
+    type IntoIter = alloc::vec::IntoIter<ServiceMetadata>;
+
+    /// Iterates over the metadata of the services held by the context, like `get_metadata`.
+    fn into_iter(self) -> Self::IntoIter {
+        self.get_metadata().into_iter()
+    }
+}
+
+/// Stores `instance` the way the builders do, as a type-erased `Arc<Mutex<T>>`.
+fn service_container<T: RSContextService>(instance: T) -> ServiceContainer {
+    ServiceContainer {
+        container: Box::new(Arc::new(Mutex::new(instance))) as ContainerStruct,
+        metadata: metadata_of::<T>(),
+        health_check: Some(check_health::<T>),
+    }
 }
 
 impl RSContext
     {
+    /// Finds a stored service in this context, falling through to the parent contexts.
+    fn find_service(&self, type_id: TypeId) -> Option<&ServiceContainer> {
+        self.service_map
+            .get(&type_id)
+            .or_else(|| self.parent_maps.iter().find_map(|parent| parent.get(&type_id)))
+    }
+
+    /// Creates a context holding just `instance`, without running any of its hooks.
+    /// Meant for unit tests that need a minimal context, synchronously on every backend.
+    /// `TestContextBuilder` (`test-util` feature) does the same for several services.
+    pub fn with_service<T: RSContextService>(instance: T) -> RSContext {
+        let mut service_map = MapForContainer::new();
+        service_map.insert(TypeId::of::<T>(), service_container(instance));
+        RSContext {
+            service_map: Arc::new(service_map),
+            ..RSContext::default()
+        }
+    }
+
+    /// Creates a builder for a child context layered over this one.
+    /// The child shares this context's services by Arc and may register its own (e.g. request-scoped)
+    /// services, which shadow the parent's; lookups of types the child doesn't have fall through to the parent.
+    /// The child starts with the `()` category.
+    pub fn child(&self) -> RSContextBuilder {
+        let mut parent_maps = vec![Arc::clone(&self.service_map)];
+        parent_maps.extend(self.parent_maps.iter().cloned());
+        RSContextBuilder::with_parent_maps(parent_maps)
+    }
+
     /// Retrieves a shared, mutex-guarded service instance.
     /// Cloning the Arc increments the reference count, allowing shared ownership.
+    /// Services registered with `register_lazy` are created on the first call (vanilla only;
+    /// async backends create them in `try_call`). Use `try_call` to see why a lazy service failed.
+    /// In tokio debug builds, panics if the current task already holds T's lock through the context
+    /// (e.g. from T's own hook), since locking the returned Arc would deadlock; `call_weak` doesn't check.
     pub fn call<T>(&self) -> Option<Arc<Mutex<T>>>
     where
         T: RSContextService, // T must be a registered service type
     {
+        #[cfg(any(feature = "tokio", feature = "async-std"))]
+        reentrancy::assert_not_held::<T>();
+        self.find_mutex::<T>()
+    }
+
+    /// Looks up a mutex-guarded service like `call`, without the re-entrancy check.
+    fn find_mutex<T>(&self) -> Option<Arc<Mutex<T>>>
+    where
+        T: RSContextService,
+    {
+        let boxed_val = self.find_service(TypeId::of::<T>())?;
+        if let Some(service) = boxed_val.container.downcast_ref::<Arc<Mutex<T>>>() {
+            return Some(service.clone());
+        }
+        boxed_val.container
+            .downcast_ref::<LazyService<T>>()
+            .and_then(|lazy| lazy.resolve(self).ok())
+    }
+
+    /// Retrieves a service like `call`, creating it first if it was registered with `register_lazy`.
+    /// Fails with `NotFound` if the service is missing, or with the `HookFailed` error of a lazy
+    /// service whose `on_all_services_built` hook failed (every later call returns it again).
+    #[cfg(not(any(feature = "tokio", feature = "async-std")))]
+    pub fn try_call<T>(&self) -> Result<Arc<Mutex<T>>, RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let not_found = || RsServiceError::NotFound(core::any::type_name::<T>().to_string());
+        let boxed_val = self.find_service(TypeId::of::<T>()).ok_or_else(not_found)?;
+        if let Some(service) = boxed_val.container.downcast_ref::<Arc<Mutex<T>>>() {
+            return Ok(service.clone());
+        }
+        boxed_val.container
+            .downcast_ref::<LazyService<T>>()
+            .ok_or_else(not_found)?
+            .resolve(self)
+    }
+
+    /// Retrieves a service like `call`, creating it first if it was registered with `register_lazy`.
+    /// Fails with `NotFound` if the service is missing, or with the `HookFailed` error of a lazy
+    /// service whose `on_all_services_built` hook failed (every later call returns it again).
+    /// In tokio debug builds, panics like `call` if the current task already holds T's lock.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn try_call<T>(&self) -> Result<Arc<Mutex<T>>, RsServiceError>
+    where
+        T: RSContextService,
+    {
+        reentrancy::assert_not_held::<T>();
+        let not_found = || RsServiceError::NotFound(core::any::type_name::<T>().to_string());
+        let boxed_val = self.find_service(TypeId::of::<T>()).ok_or_else(not_found)?;
+        if let Some(service) = boxed_val.container.downcast_ref::<Arc<Mutex<T>>>() {
+            return Ok(service.clone());
+        }
+        boxed_val.container
+            .downcast_ref::<LazyService<T>>()
+            .ok_or_else(not_found)?
+            .resolve_async(self)
+            .await
+    }
+
+    /// Whether service T is registered in this context or one of its parents.
+    pub fn contains<T>(&self) -> bool
+    where
+        T: RSContextService,
+    {
+        self.find_service(TypeId::of::<T>()).is_some()
+    }
+
+    /// Retrieves a weak handle to a service, for services that refer to each other
+    /// (e.g. observers) without keeping each other alive in an `Arc` cycle.
+    /// Store the `Weak` and upgrade it on use: `if let Some(peer) = weak.upgrade() { ... }`;
+    /// the upgrade fails once the context and every other `Arc` to the service are dropped.
+    pub fn call_weak<T>(&self) -> Option<Weak<Mutex<T>>>
+    where
+        T: RSContextService,
+    {
+        self.find_mutex::<T>().map(|service| Arc::downgrade(&service))
+    }
+
+    /// Runs `f` on a borrow of the stored service `Arc` instead of cloning it like `call` does,
+    /// which saves the reference count update for transient uses. None if the service is missing.
+    /// Lazy services are still handed out as a clone.
+    pub fn call_with<T, R>(&self, f: impl FnOnce(&Arc<Mutex<T>>) -> R) -> Option<R>
+    where
+        T: RSContextService,
+    {
+        let boxed_val = self.find_service(TypeId::of::<T>())?;
+        if let Some(service) = boxed_val.container.downcast_ref::<Arc<Mutex<T>>>() {
+            return Some(f(service));
+        }
+        self.call::<T>().as_ref().map(f)
+    }
+
+    /// Retrieves a service registered with `register_shared`, which needs no lock.
+    pub fn call_shared<T>(&self) -> Option<Arc<T>>
+    where
+        T: RSContextService,
+    {
+        self.find_service(TypeId::of::<T>())?
+            .container
+            .downcast_ref::<Arc<T>>()
+            .cloned()
+    }
+
+    /// Retrieves the trait object registered with `register_dyn::<Dyn>`, e.g. `call_dyn::<dyn Greeter + Send>()`,
+    /// so callers depend on the trait rather than the concrete service.
+    pub fn call_dyn<Dyn>(&self) -> Option<Arc<Mutex<Box<Dyn>>>>
+    where
+        Dyn: ?Sized + Send + 'static,
+    {
+        self.find_service(TypeId::of::<Dyn>())?
+            .container
+            .downcast_ref::<Arc<Mutex<Box<Dyn>>>>()
+            .cloned()
+    }
+
+    /// Iterates over the `register_dyn::<Dyn>` trait objects of this context and then of its parents (nearest first).
+    /// Each `Arc` is cloned only when the iterator reaches it, so taking the first match costs a single clone.
+    pub fn iter_services_by_trait<Dyn>(&self) -> impl Iterator<Item = Arc<Mutex<Box<Dyn>>>> + '_
+    where
+        Dyn: ?Sized + Send + 'static,
+    {
+        core::iter::once(&*self.service_map)
+            .chain(self.parent_maps.iter().map(|parent| &**parent))
+            .filter_map(|map| map.get(&TypeId::of::<Dyn>()))
+            .filter_map(|service| service.container.downcast_ref::<Arc<Mutex<Box<Dyn>>>>().cloned())
+    }
+
+    /// Retrieves a function registered with `register_from_fn`.
+    /// Returns None if no function has that name, or if its argument and return types aren't `A` and `R`.
+    /// Functions are not inherited by child contexts.
+    pub fn call_fn<A: 'static, R: 'static>(&self, name: &str) -> Option<SharedFn<A, R>> {
+        self.functions.get(name)?.downcast_ref::<SharedFn<A, R>>().cloned()
+    }
+
+    /// Retrieves two services at once, in type parameter order; None if either is missing.
+    pub fn call2<A, B>(&self) -> Option<(SharedService<A>, SharedService<B>)>
+    where
+        A: RSContextService,
+        B: RSContextService,
+    {
+        Some((self.call::<A>()?, self.call::<B>()?))
+    }
+
+    /// Retrieves three services at once, in type parameter order; None if any is missing.
+    pub fn call3<A, B, C>(&self) -> Option<(SharedService<A>, SharedService<B>, SharedService<C>)>
+    where
+        A: RSContextService,
+        B: RSContextService,
+        C: RSContextService,
+    {
+        Some((self.call::<A>()?, self.call::<B>()?, self.call::<C>()?))
+    }
+
+    /// Retrieves a service like `call`, or a fresh `T::default()` if it isn't registered.
+    /// The default is not stored: each call gets its own un-shared instance, and changes to it
+    /// are not seen by other callers. A `register_lazy` service is created first and handed out,
+    /// falling back to the default only if its `on_all_services_built` hook failed.
+    #[cfg(not(any(feature = "tokio", feature = "async-std")))]
+    pub fn call_or_default<T>(&self) -> Arc<Mutex<T>>
+    where
+        T: RSContextService + Default,
+    {
+        self.call::<T>().unwrap_or_else(|| Arc::new(Mutex::new(T::default())))
+    }
+
+    /// Retrieves a service like `try_call`, or a fresh `T::default()` if it isn't registered.
+    /// The default is not stored: each call gets its own un-shared instance, and changes to it
+    /// are not seen by other callers. A `register_lazy` service is created first and handed out,
+    /// falling back to the default only if its `on_all_services_built` hook failed.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn call_or_default<T>(&self) -> Arc<Mutex<T>>
+    where
+        T: RSContextService + Default,
+    {
+        self.try_call::<T>().await.unwrap_or_else(|_| Arc::new(Mutex::new(T::default())))
+    }
+
+    /// Retrieves a service like `try_call`, but returns None instead of the error.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn call_lazy<T>(&self) -> Option<Arc<Mutex<T>>>
+    where
+        T: RSContextService,
+    {
+        self.try_call::<T>().await.ok()
+    }
+
+    /// Returns a clone of the service value, holding the lock only while cloning.
+    /// Cheaper to use than a guard for small value-type services, but the clone is a snapshot:
+    /// later changes to the shared instance are not reflected in it, and vice versa.
+    /// Returns None if the service is missing or its mutex is poisoned.
+    #[cfg(not(any(feature = "tokio", feature = "async-std")))]
+    pub fn call_owned<T>(&self) -> Option<T>
+    where
+        T: RSContextService + Clone,
+    {
+        let service = self.call::<T>()?;
+        let guard = lock_service(&service, false).ok()?;
+        Some(guard.clone())
+    }
+
+    /// Returns a clone of the service value, holding the lock only while cloning.
+    /// Cheaper to use than a guard for small value-type services, but the clone is a snapshot:
+    /// later changes to the shared instance are not reflected in it, and vice versa.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn call_owned<T>(&self) -> Option<T>
+    where
+        T: RSContextService + Clone,
+    {
+        let service = self.try_call::<T>().await.ok()?;
+        reentrancy::assert_not_held::<T>();
+        let guard = service.lock().await;
+        Some(guard.clone())
+    }
+
+    /// Swaps the instance behind a registered service for `new`.
+    /// Existing `Arc` holders keep working and see the new value on their next lock.
+    /// No lifecycle hooks are run for the new instance.
+    #[cfg(not(any(feature = "tokio", feature = "async-std")))]
+    pub fn replace_service<T>(&self, new: T) -> Result<(), RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = self.try_call::<T>()?;
+        let mut guard = lock_service(&service, false)?;
+        *guard = new;
+        Ok(())
+    }
+
+    /// Swaps the instance behind a registered service for `new`.
+    /// Existing `Arc` holders keep working and see the new value on their next lock.
+    /// No lifecycle hooks are run for the new instance.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn replace_service<T>(&self, new: T) -> Result<(), RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = self.try_call::<T>().await?;
+        reentrancy::assert_not_held::<T>();
+        *service.lock().await = new;
+        Ok(())
+    }
+
+    /// Rebuilds a registered service in place, e.g. to pick up changed config: creates a new instance
+    /// with `on_register_crate_instance`, swaps it in under the lock and runs its `on_all_services_built`
+    /// with the lock still held. Existing `Arc` holders keep working with the new instance.
+    /// `on_service_created` is not run, since there is no builder anymore.
+    /// Fails with `NotFound` if the service is missing, or `HookFailed` if its hook fails
+    /// (the new instance stays in place). The hooks run with the builder's `catch_panics` and
+    /// `map_hook_errors` settings.
+    #[cfg(not(any(feature = "tokio", feature = "async-std")))]
+    pub fn reload<T>(&self) -> Result<(), RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = self.try_call::<T>()?;
+        reload_service::<T>(&service, self, HookSettings::of(&self.hook_settings))
+    }
+
+    /// Rebuilds a registered service in place, e.g. to pick up changed config: creates a new instance
+    /// with `on_register_crate_instance`, swaps it in under the lock and runs its `on_all_services_built`
+    /// with the lock still held. Existing `Arc` holders keep working with the new instance.
+    /// `on_service_created` is not run, since there is no builder anymore.
+    /// Fails with `NotFound` if the service is missing, or `HookFailed` if its hook fails
+    /// (the new instance stays in place). The hooks run with the builder's `catch_panics` and
+    /// `map_hook_errors` settings.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn reload<T>(&self) -> Result<(), RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = self.try_call::<T>().await?;
+        reload_service::<T>(&service, self, HookSettings::of(&self.hook_settings)).await
+    }
+
+    /// Runs the `on_all_services_built` hooks held back by `RSContextBuilder::build_without_hooks`,
+    /// the same way `build` would. They run once: later calls, and contexts built with `build`, do nothing.
+    #[cfg(not(any(feature = "tokio", feature = "async-std")))]
+    pub fn run_post_build_hooks(&self) -> Result<(), RsServiceError> {
+        // Take the hooks first, so a hook calling this again finds nothing to run.
+        let hooks = lock_service(&self.post_build_hooks, true)?.take();
+        match hooks {
+            Some(hooks) => hooks.run(self),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs the `on_all_services_built` hooks held back by `RSContextBuilder::build_without_hooks`,
+    /// the same way `build` would. They run once: later calls, and contexts built with `build`, do nothing.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn run_post_build_hooks(&self) -> Result<(), RsServiceError> {
+        // Take the hooks first, so a hook calling this again finds nothing to run.
+        let hooks = self.post_build_hooks.lock().await.take();
+        match hooks {
+            Some(hooks) => hooks.run(self).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Unregisters service T and returns it, e.g. to tear down one subsystem; `call::<T>()` returns None afterwards.
+    /// Existing `Arc` holders keep the instance alive. Returns None if T isn't held by this context itself
+    /// (inherited, lazy and shared services can't be taken) or if a child context or a clone still shares its services.
+    pub fn take_service<T>(&mut self) -> Option<Arc<Mutex<T>>>
+    where
+        T: RSContextService,
+    {
+        let service_map = Arc::get_mut(&mut self.service_map)?;
+        let type_id = TypeId::of::<T>();
+        if !service_map.get(&type_id)?.container.is::<Arc<Mutex<T>>>() {
+            return None;
+        }
+        let service = service_map.remove(&type_id)?.container.downcast::<Arc<Mutex<T>>>().ok()?;
+        Some(*service)
+    }
+
+    /// Looks up the service, locks it and runs `f` on it, returning what `f` returns.
+    /// Fails with `NotFound` if the service is missing or `LockPoisoned` if its mutex is poisoned.
+    /// A lazy service is created first, failing like `try_call` if its hook fails.
+    #[cfg(not(any(feature = "tokio", feature = "async-std")))]
+    pub fn call_map<T, R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = self.try_call::<T>()?;
+        let mut guard = lock_service(&service, false)?;
+        Ok(f(&mut guard))
+    }
+
+    /// Looks up the service, locks it and runs `f` on it, returning what `f` returns.
+    /// Fails with `NotFound` if the service is missing. A lazy service is created first,
+    /// failing like `try_call` if its hook fails.
+    /// In debug builds on tokio, locking a service the task already holds through the context
+    /// (e.g. from inside `f` or the service's own hook) panics instead of deadlocking.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn call_map<T, R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = self.try_call::<T>().await?;
+        reentrancy::assert_not_held::<T>();
+        let mut guard = service.lock().await;
+        Ok(reentrancy::holding_sync::<T, _>(|| f(&mut guard)))
+    }
+
+    /// Looks up the service, locks it and awaits `f` on it, e.g.
+    /// `ctx.call_map_async(async |svc: &mut MyService| svc.do_thing().await)`.
+    /// The lock is held until the future completes. Fails with `NotFound` if the service is missing.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn call_map_async<T, R>(&self, f: impl AsyncFnOnce(&mut T) -> R) -> Result<R, RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = self.try_call::<T>().await?;
+        reentrancy::assert_not_held::<T>();
+        let mut guard = service.lock().await;
+        Ok(reentrancy::holding::<T, _>(f(&mut guard)).await)
+    }
+
+    /// Locks the service with a time limit and runs `f` on it.
+    /// Returns `Timeout` if the lock isn't acquired within `dur`, so a contended service
+    /// can't block the caller indefinitely.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn with_service_timeout<T, R>(&self, dur: Duration, f: impl FnOnce(&mut T) -> R) -> Result<R, RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = self.try_call::<T>().await?;
+        let mut guard = async_runtime::timeout(dur, service.lock())
+            .await
+            .ok_or_else(|| RsServiceError::Timeout(format!("locking {}", core::any::type_name::<T>())))?;
+        Ok(reentrancy::holding_sync::<T, _>(|| f(&mut guard)))
+    }
+
+    /// Merges two independently built contexts into one holding the services of both.
+    /// Fails with `AlreadyRegistered` if both contexts hold the same service type.
+    /// Categories are compatible when at most one of them is set (not `()`); otherwise the merge fails.
+    /// Neither context may have child contexts or clones still sharing its services.
+    /// Hooks that `other` holds back from `build_without_hooks` are dropped, so run them first.
+    pub fn merge(self, other: RSContext) -> Result<RSContext, RsServiceError> {
+        if let Some(duplicate) = other.service_map.values().find(|service| self.service_map.contains_key(&service.metadata.type_id)) {
+            return Err(RsServiceError::AlreadyRegistered(duplicate.metadata.type_name.to_string()));
+        }
+        let (category, category_name, category_to_json) = match (self.category.is::<()>(), other.category.is::<()>()) {
+            (_, true) => (self.category, self.category_name, self.category_to_json),
+            (true, false) => (other.category, other.category_name, other.category_to_json),
+            (false, false) => return Err(RsServiceError::Other("Cannot merge contexts that both have a category".to_string())),
+        };
+        if let Some(duplicate) = other.functions.keys().find(|name| self.functions.contains_key(*name)) {
+            return Err(RsServiceError::AlreadyRegistered(duplicate.clone()));
+        }
+        let shared_error = || RsServiceError::Other("Cannot merge a context whose services are shared with a child context or a clone".to_string());
+        let mut service_map = Arc::try_unwrap(self.service_map).map_err(|_| shared_error())?;
+        service_map.append(&mut Arc::try_unwrap(other.service_map).map_err(|_| shared_error())?);
+        let mut functions = Arc::try_unwrap(self.functions).map_err(|_| shared_error())?;
+        functions.append(&mut Arc::try_unwrap(other.functions).map_err(|_| shared_error())?);
+
+        let mut parent_maps = self.parent_maps;
+        parent_maps.extend(other.parent_maps);
+        Ok(RSContext {
+            service_map: Arc::new(service_map),
+            parent_maps,
+            category,
+            category_name,
+            category_to_json,
+            functions: Arc::new(functions),
+            post_build_hooks: self.post_build_hooks,
+            hook_settings: self.hook_settings,
+        })
+    }
+
+    /// Number of services held by this context, not counting those inherited from a parent context.
+    pub fn len(&self) -> usize {
+        self.service_map.len()
+    }
+
+    /// Whether this context holds no services of its own.
+    pub fn is_empty(&self) -> bool {
+        self.service_map.is_empty()
+    }
+
+    /// Returns the category set with `set_category`, if it is of type `TC`.
+    pub fn get_category<TC: Any>(&self) -> Option<&TC> {
+        self.category.downcast_ref::<TC>()
+    }
+
+    /// Serializes the category to JSON, if it was set with `set_category_json`.
+    #[cfg(feature = "serde")]
+    pub fn category_to_json(&self) -> Option<String> {
+        (self.category_to_json?)(&*self.category)
+    }
+
+    /// TypeId of the category set with `set_category`; `TypeId::of::<()>()` if none was set.
+    pub fn category_type_id(&self) -> TypeId {
+        (*self.category).type_id()
+    }
+
+    /// Returns the metadata of every service held by this context.
+    /// Unlike compile-time discovery, this only lists services that were actually registered.
+    /// Services inherited from a parent context are not included.
+    pub fn get_metadata(&self) -> Vec<ServiceMetadata> {
         self.service_map
-            .get(&TypeId::of::<T>())
-            .and_then(|boxed_val| {
-                boxed_val.container.downcast_ref::<Arc<Mutex<T>>>()
+            .values()
+            .map(|service| service.metadata.clone())
+            .collect()
+    }
+
+    /// Type names of the services held by this context, e.g. for an "Initialized services: ..." log line.
+    /// Lighter than `get_metadata`; services inherited from a parent context are not included.
+    pub fn service_type_names(&self) -> Vec<&'static str> {
+        self.service_map
+            .values()
+            .map(|service| service.metadata.type_name)
+            .collect()
+    }
+
+    /// Iterates over every stored service without cloning.
+    /// Each value is type-erased, so downcasting requires knowing the concrete type and how it was
+    /// registered; pair it with `get_metadata` to find out which. The stored shapes are:
+    /// - `Arc<Mutex<T>>` for `register`, `register_instance` and the other mutex-guarded services,
+    /// - `Arc<T>` for `register_shared`,
+    /// - `Arc<Mutex<Box<Dyn>>>` for `register_dyn::<Dyn>`, keyed by the TypeId of `Dyn`,
+    /// - a crate-private lazy slot for `register_lazy`, which can't be downcast; use `call` or `try_call` instead.
+    pub fn iter_services(&self) -> impl Iterator<Item = (TypeId, &ContainerStruct)> {
+        self.service_map
+            .iter()
+            .map(|(type_id, service)| (*type_id, &service.container))
+    }
+
+    /// Runs `health_check` on every service held by this context, one lock at a time,
+    /// and reports each failure with the TypeId of its service.
+    /// Lazy services are checked once created, and report their failed hook; services inherited
+    /// from a parent context are not checked.
+    #[cfg(not(any(feature = "tokio", feature = "async-std")))]
+    pub fn check_all_health(&self) -> Result<(), Vec<(TypeId, RsServiceError)>> {
+        let settings = HookSettings::of(&self.hook_settings);
+        let errors: Vec<(TypeId, RsServiceError)> = self.service_map
+            .iter()
+            .filter_map(|(type_id, service)| {
+                let check = service.health_check?;
+                check(&service.container, settings.catch_panics()).err().map(|e| (*type_id, settings.map_error(e)))
             })
-            .cloned()
+            .collect();
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Runs `health_check` on every service held by this context, one lock at a time,
+    /// and reports each failure with the TypeId of its service.
+    /// Lazy services are checked once created, and report their failed hook; services inherited
+    /// from a parent context are not checked.
+    #[cfg(any(feature = "tokio", feature = "async-std"))]
+    pub async fn check_all_health(&self) -> Result<(), Vec<(TypeId, RsServiceError)>> {
+        let settings = HookSettings::of(&self.hook_settings);
+        let mut errors = Vec::new();
+        for (type_id, service) in self.service_map.iter() {
+            if let Some(check) = service.health_check
+                && let Err(e) = check(&service.container, settings.catch_panics()).await
+            {
+                errors.push((*type_id, settings.map_error(e)));
+            }
+        }
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    /// Wraps the context in a `FrozenContext`, which can only look services up,
+    /// so a production context can't be changed by `replace_service` or `reload`.
+    pub fn freeze(self) -> FrozenContext {
+        FrozenContext { context: self }
+    }
+}
+
+/// A read-only RSContext, created with `RSContext::freeze`.
+/// It hands out services but has none of the APIs that swap or rebuild them.
+pub struct FrozenContext {
+    context: RSContext,
+}
+
+impl FrozenContext {
+    /// Retrieves a shared, mutex-guarded service instance, like `RSContext::call`.
+    pub fn call<T>(&self) -> Option<Arc<Mutex<T>>>
+    where
+        T: RSContextService,
+    {
+        self.context.call::<T>()
+    }
+
+    /// Whether service T is registered, like `RSContext::contains`.
+    pub fn contains<T>(&self) -> bool
+    where
+        T: RSContextService,
+    {
+        self.context.contains::<T>()
+    }
+
+    /// Returns the metadata of every service held by the context, like `RSContext::get_metadata`.
+    pub fn get_metadata(&self) -> Vec<ServiceMetadata> {
+        self.context.get_metadata()
+    }
+
+    /// Gives the mutable context back, e.g. for tests that need to swap a service.
+    pub fn unfreeze(self) -> RSContext {
+        self.context
     }
 }
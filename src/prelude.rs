@@ -0,0 +1,6 @@
+// The items most services need, re-exported for the active backend: `use rs_ervice::prelude::*;`.
+// `RSContextBuilder` and `RSContextService` resolve to the vanilla, tokio or async-std versions
+// depending on the enabled feature, so service files don't need per-feature import paths.
+
+pub use crate::{impl_rs_service, RSContext, RSContextBuilder, RSContextService, RsServiceError};
+pub use rs_ervice_macro_lib::{r_service, r_service_struct, RSContextService};
@@ -0,0 +1,62 @@
+// Re-entrant lock detection for the tokio backend, in debug builds only.
+// tokio::sync::Mutex is not re-entrant: a task locking a service it already holds waits forever.
+// The context's own locking APIs record the services the current task holds in a task-local,
+// so a second lock of the same service panics with its name instead of hanging.
+// Locks taken directly on an `Arc<Mutex<T>>` are not tracked, so `call` and `try_call` check instead:
+// a task asking for a service whose lock it holds would deadlock on locking what they return.
+// In release builds, and on other backends, every helper just runs the step.
+
+use crate::RSContextService;
+#[cfg(all(feature = "tokio", debug_assertions))]
+use std::any::TypeId;
+
+#[cfg(all(feature = "tokio", debug_assertions))]
+tokio::task_local! {
+    /// Services whose lock the current task holds through the context.
+    static HELD_SERVICES: Vec<TypeId>;
+}
+
+/// Services held by the current task, plus T.
+#[cfg(all(feature = "tokio", debug_assertions))]
+fn held_with<T: RSContextService>() -> Vec<TypeId> {
+    let mut held = HELD_SERVICES.try_with(Clone::clone).unwrap_or_default();
+    held.push(TypeId::of::<T>());
+    held
+}
+
+/// Panics if the current task already holds T's lock through the context.
+pub(crate) fn assert_not_held<T: RSContextService>() {
+    #[cfg(all(feature = "tokio", debug_assertions))]
+    if HELD_SERVICES.try_with(|held| held.contains(&TypeId::of::<T>())).unwrap_or(false) {
+        panic!(
+            "re-entrant lock of service {}: this task already holds its lock, so locking it again would deadlock",
+            std::any::type_name::<T>()
+        );
+    }
+}
+
+/// Runs `f` while T's lock is recorded as held by the current task.
+#[cfg_attr(not(all(feature = "tokio", debug_assertions)), allow(clippy::extra_unused_type_parameters))]
+pub(crate) fn holding_sync<T: RSContextService, R>(f: impl FnOnce() -> R) -> R {
+    #[cfg(all(feature = "tokio", debug_assertions))]
+    {
+        HELD_SERVICES.sync_scope(held_with::<T>(), f)
+    }
+    #[cfg(not(all(feature = "tokio", debug_assertions)))]
+    {
+        f()
+    }
+}
+
+/// Awaits `future` while T's lock is recorded as held by the current task.
+#[cfg_attr(not(all(feature = "tokio", debug_assertions)), allow(clippy::extra_unused_type_parameters))]
+pub(crate) async fn holding<T: RSContextService, F: Future>(future: F) -> F::Output {
+    #[cfg(all(feature = "tokio", debug_assertions))]
+    {
+        HELD_SERVICES.scope(held_with::<T>(), future).await
+    }
+    #[cfg(not(all(feature = "tokio", debug_assertions)))]
+    {
+        future.await
+    }
+}
@@ -0,0 +1,33 @@
+// Assembles a context of mocks for unit tests, enabled with the `test-util` feature.
+// Like `RSContext::with_service`, no lifecycle hook runs, and it is synchronous on every backend.
+
+use crate::common::MapForContainer;
+use crate::{service_container, RSContext, RSContextService};
+use alloc::sync::Arc;
+use core::any::TypeId;
+
+/// Chains `.with::<A>(a).with::<B>(b)` and `.finish()`es into an `RSContext`, running no hooks.
+#[derive(Default)]
+pub struct TestContextBuilder {
+    service_map: MapForContainer,
+}
+
+impl TestContextBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `instance` as service T, replacing an earlier instance of the same type.
+    pub fn with<T: RSContextService>(mut self, instance: T) -> Self {
+        self.service_map.insert(TypeId::of::<T>(), service_container(instance));
+        self
+    }
+
+    /// Creates the context holding every added service.
+    pub fn finish(self) -> RSContext {
+        RSContext {
+            service_map: Arc::new(self.service_map),
+            ..RSContext::default()
+        }
+    }
+}
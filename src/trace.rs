@@ -0,0 +1,43 @@
+// Spans for registration, hooks and build, emitted with the `tracing` feature.
+// Without the feature each helper just runs the step, so there is no runtime cost.
+
+/// Runs a step inside an `rs_ervice` span with the step and service type name,
+/// then logs how long it took.
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+pub(crate) fn traced<R>(step: &'static str, service: &'static str, f: impl FnOnce() -> R) -> R {
+    #[cfg(feature = "tracing")]
+    {
+        let span = tracing::debug_span!("rs_ervice", step, service);
+        let _entered = span.enter();
+        let start = std::time::Instant::now();
+        let output = f();
+        tracing::debug!(elapsed = ?start.elapsed(), "{step} finished");
+        output
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (step, service);
+        f()
+    }
+}
+
+/// Awaits a step inside an `rs_ervice` span with the step and service type name,
+/// then logs how long it took. The span is attached to the future, so it is entered
+/// on every poll and follows the step across `.await` points.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+pub(crate) async fn traced<F: Future>(step: &'static str, service: &'static str, future: F) -> F::Output {
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::Instrument;
+        let span = tracing::debug_span!("rs_ervice", step, service);
+        let start = std::time::Instant::now();
+        let output = future.instrument(span.clone()).await;
+        span.in_scope(|| tracing::debug!(elapsed = ?start.elapsed(), "{step} finished"));
+        output
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (step, service);
+        future.await
+    }
+}
@@ -1,10 +1,24 @@
 // --- Conditional Mutex and Arc ---
-use std::sync::Mutex;
-use std::any::{Any, TypeId};
-use std::collections::BTreeMap;
-use std::sync::Arc;
-use crate::common::{RsServiceError,MapForContainer, ContainerStruct};
+#[cfg(not(any(feature = "spin", feature = "parking_lot")))]
+use std::sync::{Mutex, MutexGuard, OnceLock};
+#[cfg(feature = "parking_lot")]
+use parking_lot::{Mutex, MutexGuard};
+#[cfg(feature = "parking_lot")]
+use std::sync::OnceLock;
+#[cfg(feature = "spin")]
+use spin::{Mutex, MutexGuard, Once as OnceLock};
+use core::any::{Any, TypeId};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+#[cfg(feature = "spin")]
+use alloc::{boxed::Box, format, string::{String, ToString}, vec, vec::Vec};
+#[cfg(not(feature = "spin"))]
+use crate::common::panic_error;
+#[cfg(feature = "serde")]
+use crate::common::category_to_json;
+use crate::common::{CategoryToJson, RsServiceError,MapForContainer, MapForFunctions, ContainerStruct, ServiceContainer, ServiceMetadata, SharedFn};
 use crate::RSContext;
+use crate::trace::traced;
 
 pub trait RSContextService: Any + Send + Sync + 'static {
     /// Called by the framework to get a new instance of the service.
@@ -12,34 +26,313 @@ pub trait RSContextService: Any + Send + Sync + 'static {
     fn on_register_crate_instance() -> Self where Self: Sized;
 
     /// Called after the service instance is created and before it's wrapped
-    /// in Arc<Mutex<T>> and stored in the builder.
+    /// in `Arc<Mutex<T>>` and stored in the builder.
     /// Ideal for initial setup that might need mutable access to self
     /// or access to builder configurations.
-    fn on_service_created(&mut self, builder: &RSContextBuilder) -> Result<(), RsServiceError>;
+    /// Dependencies can be pulled in with `builder.request_register::<Dep>()`.
+    fn on_service_created(&mut self, builder: &mut RSContextBuilder) -> Result<(), RsServiceError>;
 
     /// (Optional) Called after all services are built and the RSContext is ready.
     /// This hook would be called on `&self` (obtained via MutexGuard).
+    /// Hooks run one at a time by descending `priority`, then in registration order,
+    /// only after every service has been created,
+    /// and only this service's lock is held meanwhile: other services can be locked through
+    /// `context`, but locking this service again from its own hook deadlocks.
+    /// The context is complete before the first hook runs: every registered service is callable
+    /// and listed by `context.get_metadata()`, so a service may enumerate its peers here.
     fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError>;
 
+    /// (Optional) Reports whether the service is healthy, e.g. for a readiness probe.
+    /// Called by `RSContext::check_all_health` with this service's lock held.
+    fn health_check(&self) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+
+    /// (Optional) TypeIds of the services this service depends on.
+    /// The builder creates those services first, so `on_service_created` can rely on them;
+    /// `build` fails if one of them was never registered.
+    fn dependencies() -> Vec<TypeId> where Self: Sized {
+        Vec::new()
+    }
+
+    /// (Optional) TypeIds of services this service uses if they are registered, e.g. a metrics
+    /// exporter that enhances its peers. Unlike `dependencies`, `build` doesn't fail without them.
+    /// A peer that is registered but still waiting for its own dependencies is created first,
+    /// unless it waits for this service, in which case it is created after; check whether one exists with `call_pending` or `RSContext::contains`.
+    fn optional_dependencies() -> Vec<TypeId> where Self: Sized {
+        Vec::new()
+    }
+
+    /// (Optional) Orders `on_all_services_built` hooks: higher priorities run first.
+    /// Only that hook is reordered; `on_service_created` still runs as each service is registered,
+    /// after its `dependencies`. So a service with a higher priority than its dependency
+    /// has its `on_all_services_built` run first, even though it was created after the dependency.
+    fn priority() -> i32 where Self: Sized {
+        0
+    }
+
+    /// (Optional) TypeId of the category this service reads with `get_category` or `with_category_mut`.
+    /// `RSContextBuilder::validate` reports the service if the builder's category is of another type.
+    fn category_type() -> Option<TypeId> where Self: Sized {
+        None
+    }
+
+    /// (Optional) Logical name used in logs, spans and `ServiceMetadata::name`, instead of the
+    /// fully-qualified type path. `#[r_service(auto_impl)]` returns the `#[r_service_struct(name = "...")]` name.
+    fn name() -> &'static str where Self: Sized {
+        core::any::type_name::<Self>()
+    }
 }
 
+/// The mutex guarding each service: `std::sync::Mutex`, or `spin::Mutex` / `parking_lot::Mutex` with those features.
+pub type ServiceMutex<T> = Mutex<T>;
+
+/// Locks a service, failing with `LockPoisoned` if a thread panicked while holding it,
+/// unless `recover_poisoned` is set. spin and parking_lot mutexes are never poisoned.
+pub(crate) fn lock_service<T>(service: &Mutex<T>, recover_poisoned: bool) -> Result<MutexGuard<'_, T>, RsServiceError> {
+    #[cfg(not(any(feature = "spin", feature = "parking_lot")))]
+    return match service.lock() {
+        Ok(guard) => Ok(guard),
+        Err(poisoned) if recover_poisoned => Ok(poisoned.into_inner()),
+        Err(_) => Err(RsServiceError::LockPoisoned(core::any::type_name::<T>().to_string())),
+    };
+    #[cfg(any(feature = "spin", feature = "parking_lot"))]
+    {
+        let _ = recover_poisoned;
+        Ok(service.lock())
+    }
+}
+
+/// Metadata of service T, named by `T::name()`.
+pub(crate) fn metadata_of<T: RSContextService>() -> ServiceMetadata {
+    ServiceMetadata::of::<T>().with_name(T::name())
+}
+
+/// Locks the service stored in `container` and runs its `health_check`, catching a panic in it
+/// if `catch_panics` is set. Services registered with `register_shared` are checked without a lock.
+/// Lazy services are checked once created, and report the failure of their `on_all_services_built` hook.
+pub(crate) fn check_health<T: RSContextService>(container: &ContainerStruct, catch_panics: bool) -> Result<(), RsServiceError> {
+    if let Some(service) = container.downcast_ref::<Arc<T>>() {
+        return run_hook::<T, _>(catch_panics, "health_check", || service.health_check());
+    }
+    let service = match container.downcast_ref::<LazyService<T>>() {
+        Some(lazy) => match lazy.cell.get() {
+            Some(created) => created.as_ref().map_err(Clone::clone)?,
+            None => return Ok(()),
+        },
+        None => match container.downcast_ref::<Arc<Mutex<T>>>() {
+            Some(service) => service,
+            None => return Ok(()),
+        },
+    };
+    let guard = lock_service(service, false)?;
+    run_hook::<T, _>(catch_panics, "health_check", || guard.health_check())
+}
+
+/// Swaps a new instance into `service` and runs its `on_all_services_built` hook with the lock held,
+/// for `RSContext::reload`. Both hooks run with the context's builder settings, like lazy services.
+pub(crate) fn reload_service<T: RSContextService>(service: &Mutex<T>, context: &RSContext, settings: &HookSettings) -> Result<(), RsServiceError> {
+    let catch_panics = settings.catch_panics;
+    let reloaded = traced("on_register_crate_instance", T::name(), || {
+        run_hook::<T, _>(catch_panics, "on_register_crate_instance", || Ok(T::on_register_crate_instance()))
+    }).and_then(|instance| {
+        let mut guard = lock_service(service, false)?;
+        *guard = instance;
+        traced("on_all_services_built", T::name(), || {
+            run_hook::<T, _>(catch_panics, "on_all_services_built", || guard.on_all_services_built(context))
+        })
+    });
+    reloaded.map_err(|e| settings.map_error(e))
+}
+
+/// Storage for a service registered with `register_lazy`.
+/// The instance is created on the first `call::<T>()` and cached;
+/// if its `on_all_services_built` hook fails, the error is cached instead.
+pub(crate) struct LazyService<T> {
+    cell: OnceLock<Result<Arc<Mutex<T>>, RsServiceError>>,
+    /// Hook settings of the builder that registered the service, filled in by `build`.
+    settings: SharedHookSettings,
+}
+
+impl<T: RSContextService> LazyService<T> {
+    /// Returns the cached instance, creating it and running its `on_all_services_built` hook
+    /// the way `build` runs eager hooks on first use.
+    pub(crate) fn resolve(&self, context: &RSContext) -> Result<Arc<Mutex<T>>, RsServiceError> {
+        let create = || {
+            let settings = HookSettings::of(&self.settings);
+            let catch_panics = settings.catch_panics;
+            let built = traced("on_register_crate_instance", T::name(), || {
+                run_hook::<T, _>(catch_panics, "on_register_crate_instance", || Ok(T::on_register_crate_instance()))
+            }).and_then(|instance| {
+                let service = Arc::new(Mutex::new(instance));
+                let guard = lock_service(&service, false)?;
+                traced("on_all_services_built", T::name(), || {
+                    run_hook::<T, _>(catch_panics, "on_all_services_built", || guard.on_all_services_built(context))
+                })?;
+                drop(guard);
+                Ok(service)
+            });
+            built.map_err(|e| settings.map_error(e))
+        };
+        #[cfg(not(feature = "spin"))]
+        let cached = self.cell.get_or_init(create);
+        #[cfg(feature = "spin")]
+        let cached = self.cell.call_once(create);
+        cached.clone()
+    }
+}
+
+/// Rewrites hook failures, e.g. to add a correlation id; set with `map_hook_errors`.
+type HookErrorMapper = Arc<dyn Fn(TypeId, RsServiceError) -> RsServiceError + Send + Sync>;
+
+/// The builder settings hooks run with after `build`: by lazy services, which are created on first use,
+/// and by `RSContext::check_all_health`.
+pub(crate) struct HookSettings {
+    catch_panics: bool,
+    hook_error_mapper: Option<HookErrorMapper>,
+}
+
+/// Settings of contexts made without a builder, e.g. by `RSContext::with_service`.
+static DEFAULT_HOOK_SETTINGS: HookSettings = HookSettings { catch_panics: true, hook_error_mapper: None };
+
+/// Builder settings shared by a builder, its lazy services and the context it builds, filled in by `build`.
+pub(crate) type SharedHookSettings = Arc<OnceLock<HookSettings>>;
+
+impl HookSettings {
+    /// The settings in `settings`, or the defaults if no builder filled them in.
+    pub(crate) fn of(settings: &OnceLock<HookSettings>) -> &HookSettings {
+        settings.get().unwrap_or(&DEFAULT_HOOK_SETTINGS)
+    }
+
+    /// Whether panics in hooks are returned as errors instead of unwinding.
+    pub(crate) fn catch_panics(&self) -> bool {
+        self.catch_panics
+    }
+
+    /// Passes a hook failure through the builder's hook error mapper, if one was set.
+    pub(crate) fn map_error(&self, error: RsServiceError) -> RsServiceError {
+        map_hook_error(&self.hook_error_mapper, error)
+    }
+}
+
+/// Passes a `HookFailed` error through the builder's hook error mapper, if one is set.
+fn map_hook_error(mapper: &Option<HookErrorMapper>, error: RsServiceError) -> RsServiceError {
+    match (mapper, &error) {
+        (Some(mapper), RsServiceError::HookFailed { type_id, .. }) => mapper(*type_id, error),
+        _ => error,
+    }
+}
+
+/// Runs a hook of service T, wrapping its error in `HookFailed`.
+/// With `catch_panics`, a panic in the hook is returned as a `HookFailed` too.
+fn run_hook<T: Any, R>(catch_panics: bool, hook: &'static str, f: impl FnOnce() -> Result<R, RsServiceError>) -> Result<R, RsServiceError> {
+    #[cfg(not(feature = "spin"))]
+    let result = if catch_panics {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).unwrap_or_else(|payload| Err(panic_error(payload)))
+    } else {
+        f()
+    };
+    #[cfg(feature = "spin")]
+    let result = {
+        let _ = catch_panics;
+        f()
+    };
+    result.map_err(|e| RsServiceError::hook_failed::<T>(hook, e))
+}
+
+/// Receives the built context, whether poisoned mutexes should be recovered
+/// and whether panics should be caught.
 type AfterBuildHook = Box<
-    dyn FnOnce(&RSContext) -> 
+    dyn FnOnce(&RSContext, bool, bool) -> 
         Result<(), RsServiceError> 
         + Send 
         + Sync
 >;
 
+/// The after-build hooks of a built context, with the builder settings they run with.
+/// `build` runs them right away; `build_without_hooks` leaves them to `RSContext::run_post_build_hooks`.
+pub(crate) struct PostBuildHooks {
+    hooks: Vec<(i32, AfterBuildHook)>,
+    recover_poisoned: bool,
+    catch_panics: bool,
+    hook_error_mapper: Option<HookErrorMapper>,
+}
+
+impl PostBuildHooks {
+    /// Runs the hooks against `context` in priority order, stopping at the first failure.
+    pub(crate) fn run(self, context: &RSContext) -> Result<(), RsServiceError> {
+        // Every service exists by now; run the hooks one at a time so each holds only its own lock.
+        for (_, hook_fn) in self.hooks {
+            hook_fn(context, self.recover_poisoned, self.catch_panics)
+                .map_err(|e| map_hook_error(&self.hook_error_mapper, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// A boxed registration step, e.g. `Box::new(|builder| builder.register::<MyService>())`,
+/// so modules can contribute services without the caller naming their types. See `extend`.
+pub type Registration = Box<
+    dyn FnOnce(RSContextBuilder) -> Result<RSContextBuilder, RsServiceError>
+>;
+
+/// A registration run against the builder in place, so a failure leaves the builder usable.
+type RequestedRegistration = Box<
+    dyn FnOnce(&mut RSContextBuilder) -> Result<(), RsServiceError>
+>;
+
+/// A registration waiting for the services it depends on to be registered.
+struct DeferredRegistration {
+    metadata: ServiceMetadata,
+    dependencies: Vec<TypeId>,
+    register: RequestedRegistration,
+}
+
 // --- RSContextBuilder: For registering and building the context ---
 #[cfg(not(feature = "tokio"))]
 /// RSContextBuilder: For registering and building the context in non-tokio environments
 pub struct RSContextBuilder {
-    /// Stores Box<Arc<Mutex<T>>> type-erased as Box<dyn Any + ...>
+    /// Stores each service type-erased; see `ServiceContainer::container` for the shapes.
     pending_services: MapForContainer,
-    /// Stores closures to run after RSContext is built.
-    after_build_hooks: Vec<AfterBuildHook>,
+    /// Stores closures to run after RSContext is built, with the service's priority,
+    /// kept sorted by descending priority.
+    after_build_hooks: Vec<(i32, AfterBuildHook)>,
     /// Placeholder for category info, can be replaced with actual type
     category_info: Box<dyn Any + Send + Sync + 'static>,
+    /// Type name of `category_info`, shown by RSContext's `Debug`.
+    category_name: &'static str,
+    /// Serializer for `category_info`, set by `set_category_json`.
+    category_to_json: Option<CategoryToJson>,
+    /// Functions registered with `register_from_fn`.
+    functions: MapForFunctions,
+    /// Registrations requested by `on_service_created` hooks, run once the requesting service is stored.
+    requested_services: Vec<RequestedRegistration>,
+    /// Registrations whose dependencies are not registered yet.
+    deferred_services: Vec<DeferredRegistration>,
+    /// Type names of the services created so far, in creation order.
+    init_order: Vec<&'static str>,
+    /// Service maps of the parent contexts when building a child context.
+    parent_maps: Vec<Arc<MapForContainer>>,
+    /// Whether after-build hooks recover a poisoned mutex instead of failing the build.
+    recover_poisoned: bool,
+    /// Applied to every hook failure before it is returned.
+    hook_error_mapper: Option<HookErrorMapper>,
+    /// Whether panics in hooks are returned as errors instead of unwinding.
+    catch_panics: bool,
+    /// Whether `build` fails when no service was registered.
+    require_non_empty: bool,
+    /// Non-fatal startup concerns reported by hooks through `warn`.
+    warnings: Vec<String>,
+    /// Type name and declared `category_type` of every registered service that declares one, for `validate`.
+    category_types: Vec<(&'static str, TypeId)>,
+    /// Shared with every lazy service, and filled in by `build` once the settings are final.
+    hook_settings: SharedHookSettings,
+}
+#[cfg(not(feature = "tokio"))]
+impl Default for RSContextBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 impl RSContextBuilder {
 
@@ -47,72 +340,652 @@ impl RSContextBuilder {
     pub fn new() -> Self {
         RSContextBuilder {
             pending_services: BTreeMap::new(),
+            init_order: Vec::new(),
             after_build_hooks: Vec::new(),
             category_info: Box::new(()), // Placeholder for category info, can be replaced with actual type
+            category_name: "()",
+            category_to_json: None,
+            functions: MapForFunctions::new(),
+            requested_services: Vec::new(),
+            deferred_services: Vec::new(),
+            parent_maps: Vec::new(),
+            recover_poisoned: false,
+            hook_error_mapper: None,
+            catch_panics: true,
+            require_non_empty: false,
+            warnings: Vec::new(),
+            category_types: Vec::new(),
+            hook_settings: Arc::new(OnceLock::new()),
+        }
+    }
+    /// Creates a new RSContextBuilder with room reserved for `service_count` services' hooks.
+    /// Useful when registering many services to avoid reallocating the hooks Vec.
+    pub fn with_service_hint(service_count: usize) -> Self {
+        let mut builder = Self::new();
+        builder.after_build_hooks.reserve(service_count);
+        builder
+    }
+    /// Creates a builder for a child context of the given parent service maps.
+    pub(crate) fn with_parent_maps(parent_maps: Vec<Arc<MapForContainer>>) -> Self {
+        let mut builder = Self::new();
+        builder.parent_maps = parent_maps;
+        builder
+    }
+    /// Whether a service type is registered, or waiting for its dependencies.
+    fn is_registered(&self, type_id: TypeId) -> bool {
+        self.pending_services.contains_key(&type_id)
+            || self.deferred_services.iter().any(|deferred| deferred.metadata.type_id == type_id)
+    }
+    /// Whether a dependency is available to the services created by this builder.
+    fn has_dependency(&self, type_id: TypeId) -> bool {
+        self.pending_services.contains_key(&type_id)
+            || self.parent_maps.iter().any(|parent| parent.contains_key(&type_id))
+    }
+    /// The services T waits for: `T::dependencies()`, plus its optional dependencies
+    /// that are registered but not created yet, unless they are themselves waiting for T.
+    fn dependencies_of<T: RSContextService>(&self) -> Vec<TypeId> {
+        let mut dependencies = T::dependencies();
+        dependencies.extend(T::optional_dependencies().into_iter().filter(|dependency| {
+            self.is_registered(*dependency)
+                && !self.has_dependency(*dependency)
+                && !self.waits_for(*dependency, TypeId::of::<T>())
+        }));
+        dependencies
+    }
+    /// Whether the deferred registration of `type_id` waits, directly or through other
+    /// deferred registrations, for `target`.
+    fn waits_for(&self, type_id: TypeId, target: TypeId) -> bool {
+        let mut stack = vec![type_id];
+        let mut visited = Vec::new();
+        while let Some(current) = stack.pop() {
+            if current == target {
+                return true;
+            }
+            if visited.contains(&current) {
+                continue;
+            }
+            visited.push(current);
+            if let Some(deferred) = self.deferred_services.iter().find(|deferred| deferred.metadata.type_id == current) {
+                stack.extend(deferred.dependencies.iter().copied());
+            }
         }
+        false
+    }
+    /// Remembers the category type T declares, if any, so `validate` can compare it with the category.
+    fn record_category_type<T: RSContextService>(&mut self) {
+        if let Some(category_type) = T::category_type() {
+            self.category_types.push((T::name(), category_type));
+        }
+    }
+    /// Fails with `AlreadyRegistered` if T is registered, or waiting for its dependencies.
+    fn ensure_unregistered<T: ?Sized + 'static>(&self) -> Result<(),RsServiceError> {
+        if self.is_registered(TypeId::of::<T>()) {
+            return Err(RsServiceError::AlreadyRegistered(core::any::type_name::<T>().to_string()));
+        }
+        Ok(())
+    }
+    /// Stages the registration of service T: fails if T is already registered, defers `register`
+    /// until `dependencies_of::<T>()` are registered, or else runs it and the deferred registrations it unblocks.
+    fn register_staged<T: RSContextService>(&mut self, register: RequestedRegistration) -> Result<(),RsServiceError> {
+        self.ensure_unregistered::<T>()?;
+        self.record_category_type::<T>();
+        let dependencies = self.dependencies_of::<T>();
+        if !dependencies.iter().all(|dependency| self.has_dependency(*dependency)) {
+            self.deferred_services.push(DeferredRegistration {
+                metadata: metadata_of::<T>(),
+                dependencies,
+                register,
+            });
+            return Ok(());
+        }
+        register(self)?;
+        self.register_ready_deferred()
     }
     #[cfg(not(feature = "tokio"))]
     /// Registers a service type T with the builder.
     /// T must implement RSContextService.
+    /// If some of `T::dependencies()` are not registered yet, T is created once they are.
+    /// Each instantiation of a generic service (e.g. `Cache<String>` and `Cache<u64>`) is a distinct service.
     pub fn register<T>(mut self) -> Result<Self,RsServiceError>
     where
         T: RSContextService, // T must implement RSContextService
     {
-        
+        self.try_register::<T>()?;
+        Ok(self)
+    }
+    /// Registers service type T like `register`, but through `&mut self`, so an error
+    /// leaves the builder usable, e.g. to collect every failed registration before giving up.
+    /// Every service registered before the error, including T if it was created, stays registered.
+    pub fn try_register<T>(&mut self) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_staged::<T>(Box::new(|builder: &mut RSContextBuilder| {
+            builder.create_service(|| Ok(T::on_register_crate_instance()))
+        }))
+    }
+    /// Registers service type T, creating it with `factory` instead of `on_register_crate_instance`,
+    /// e.g. to inject a differently configured instance or a mock per build.
+    /// `on_service_created` still runs, and `factory` is called once `T::dependencies()` are registered.
+    pub fn register_with<T>(self, factory: impl FnOnce() -> T + 'static) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_fallible(move || Ok(factory()))
+    }
+    /// Registers service type T like `register_with`, with a `factory` that can fail,
+    /// e.g. when T is built from runtime config. The factory's error is returned
+    /// as `HookFailed` for T's `on_register_crate_instance` step, so it names the service.
+    pub fn register_fallible<T>(mut self, factory: impl FnOnce() -> Result<T, RsServiceError> + 'static) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_staged::<T>(Box::new(move |builder: &mut RSContextBuilder| builder.create_service(factory)))?;
+        Ok(self)
+    }
+    /// Registers service type T stored as a plain `Arc<T>` instead of `Arc<Mutex<T>>`,
+    /// for services that never need `&mut self` once `on_service_created` has run
+    /// (e.g. a compiled regex registry), so callers share it without locking.
+    /// Retrieve it with `RSContext::call_shared`; `call` returns None for it.
+    pub fn register_shared<T>(mut self) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_staged::<T>(Box::new(|builder: &mut RSContextBuilder| builder.create_shared_service::<T>()))?;
+        Ok(self)
+    }
+    /// Registers an already constructed instance of service type T.
+    /// `on_register_crate_instance` is skipped, but `on_service_created` still runs on `value`,
+    /// and T still waits for `T::dependencies()` like `register` does.
+    pub fn register_instance<T>(mut self, value: T) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.register_staged::<T>(Box::new(move |builder: &mut RSContextBuilder| builder.store_service(value)))?;
+        Ok(self)
+    }
+    /// Applies every registration in order, stopping at the first error.
+    pub fn extend(mut self, registrations: impl IntoIterator<Item = Registration>) -> Result<Self,RsServiceError> {
+        for registration in registrations {
+            self = registration(self)?;
+        }
+        Ok(self)
+    }
+    /// Creates registrations that were waiting for dependencies which are now registered.
+    fn register_ready_deferred(&mut self) -> Result<(),RsServiceError> {
+        while let Some(index) = self.deferred_services.iter().position(|deferred| {
+            deferred.dependencies.iter().all(|dependency| self.has_dependency(*dependency))
+        }) {
+            let deferred = self.deferred_services.remove(index);
+            (deferred.register)(self)?;
+        }
+        Ok(())
+    }
+    /// Fails if `require_non_empty` is set and no service was registered.
+    fn check_non_empty(&self) -> Result<(), RsServiceError> {
+        if self.require_non_empty && self.pending_services.is_empty() {
+            return Err(RsServiceError::Other("no services registered".to_string()));
+        }
+        Ok(())
+    }
+    /// Fails with `MissingDependency` if a registration is still waiting for a dependency.
+    fn check_deferred(&self) -> Result<(), RsServiceError> {
+        match self.deferred_services.first() {
+            Some(deferred) => {
+                let dependency = deferred.dependencies.iter()
+                    .find(|dependency| !self.has_dependency(**dependency))
+                    .copied()
+                    .unwrap_or(deferred.metadata.type_id);
+                Err(RsServiceError::MissingDependency {
+                    service: deferred.metadata.type_name.to_string(),
+                    dependency,
+                })
+            }
+            None => Ok(()),
+        }
+    }
+    /// Type names of the services in the order the builder creates them: those already created,
+    /// then the deferred ones in the order their dependencies let `build` create them.
+    /// Deferred services whose dependencies are never registered, lazy services and trait objects
+    /// are left out, as are services a hook will request, since hooks haven't run yet.
+    pub fn resolution_order(&self) -> Vec<&'static str> {
+        let mut order = self.init_order.clone();
+        let mut available: Vec<TypeId> = Vec::new();
+        let mut unresolved: Vec<&DeferredRegistration> = self.deferred_services.iter().collect();
+        while let Some(index) = unresolved.iter().position(|deferred| {
+            deferred.dependencies.iter().all(|dependency| self.has_dependency(*dependency) || available.contains(dependency))
+        }) {
+            let deferred = unresolved.remove(index);
+            available.push(deferred.metadata.type_id);
+            order.push(deferred.metadata.type_name);
+        }
+        order
+    }
+    /// Checks the registrations for problems, without consuming the builder or running any hooks,
+    /// and reports them all at once: every unmet dependency, an empty builder under `require_non_empty`,
+    /// every service name (from `RSContextService::name`) shared by two service types,
+    /// and every service whose `RSContextService::category_type` isn't the type set with `set_category`.
+    pub fn validate(&self) -> Result<(), Vec<RsServiceError>> {
+        let mut errors = Vec::new();
 
-        let type_id = TypeId::of::<T>();
-        if self.pending_services.contains_key(&type_id) {
-            return Err(RsServiceError(format!("Service type {:?} already registered.", std::any::type_name::<T>())));
+        // Resolve deferred registrations the same way the builder will, then report what is left.
+        let mut available: Vec<TypeId> = Vec::new();
+        let is_available = |available: &Vec<TypeId>, type_id: &TypeId| {
+            self.has_dependency(*type_id) || available.contains(type_id)
+        };
+        let mut unresolved: Vec<&DeferredRegistration> = self.deferred_services.iter().collect();
+        while let Some(index) = unresolved.iter().position(|deferred| {
+            deferred.dependencies.iter().all(|dependency| is_available(&available, dependency))
+        }) {
+            available.push(unresolved.remove(index).metadata.type_id);
+        }
+        for deferred in unresolved {
+            for dependency in &deferred.dependencies {
+                if !is_available(&available, dependency) {
+                    errors.push(RsServiceError::MissingDependency {
+                        service: deferred.metadata.type_name.to_string(),
+                        dependency: *dependency,
+                    });
+                }
+            }
         }
-        let mut instance = T::on_register_crate_instance();
-        let result_on = instance.on_service_created(&self)
+        errors.extend(self.check_non_empty().err());
+
+        // Service types are unique by TypeId, but their logical names may collide.
+        let mut names: Vec<(&'static str, &'static str)> = self.pending_services.values()
+            .map(|service| (service.metadata.name, service.metadata.type_name))
+            .chain(self.deferred_services.iter().map(|deferred| (deferred.metadata.name, deferred.metadata.type_name)))
+            .collect();
+        names.sort_unstable();
+        for pair in names.windows(2) {
+            if pair[0].0 == pair[1].0 {
+                errors.push(RsServiceError::Other(format!(
+                    "service name {:?} is used by both {} and {}", pair[0].0, pair[0].1, pair[1].1
+                )));
+            }
+        }
+
+        let category = self.category_info.as_ref().type_id();
+        for (service, expected) in &self.category_types {
+            if *expected != category {
+                errors.push(RsServiceError::CategoryMismatch {
+                    service: service.to_string(),
+                    expected: *expected,
+                    found: self.category_name,
+                });
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+    /// Creates service T with `factory`, runs its `on_service_created` hook and stores it.
+    fn create_service<T>(&mut self, factory: impl FnOnce() -> Result<T, RsServiceError>) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = T::name();
+        traced("register", service, || {
+            let instance = self.create_instance(factory)?;
+            self.store_service(instance)
+        })
+    }
+    /// Creates service T, runs its `on_service_created` hook and stores it as a plain `Arc<T>`.
+    fn create_shared_service<T>(&mut self) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let service = T::name();
+        traced("register", service, || {
+            let instance = self.create_instance(|| Ok(T::on_register_crate_instance()))?;
+            self.store_shared_service(instance)
+        })
+    }
+    /// Creates an instance of service T with `factory`, run like a hook,
+    /// so its error or panic is returned as `HookFailed` naming T.
+    fn create_instance<T>(&self, factory: impl FnOnce() -> Result<T, RsServiceError>) -> Result<T, RsServiceError>
+    where
+        T: RSContextService,
+    {
+        traced("on_register_crate_instance", T::name(), || {
+            run_hook::<T, _>(self.catch_panics, "on_register_crate_instance", factory)
+        })
+        .map_err(|e| map_hook_error(&self.hook_error_mapper, e))
+    }
+    /// Runs the `on_service_created` hook of an instance about to be stored.
+    fn run_service_created<T>(&mut self, instance: &mut T) -> Result<(), RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let catch_panics = self.catch_panics;
+        traced("on_service_created", T::name(), || {
+            run_hook::<T, _>(catch_panics, "on_service_created", || instance.on_service_created(self))
+        })
         .map_err(
-            |e| 
-            RsServiceError(format!("on_service_created hook failed for {}: {}", std::any::type_name::<T>(), e)
-        ));
-        if let Err(e) = result_on {
+            |e| map_hook_error(&self.hook_error_mapper, e)
+        )
+    }
+    /// Adds the after-build hook of service T, ordered by `T::priority()`.
+    fn push_after_build_hook<T>(&mut self, hook: AfterBuildHook)
+    where
+        T: RSContextService,
+    {
+        // Insert after every hook of equal or higher priority, keeping registration order among equals.
+        let priority = T::priority();
+        let at = self.after_build_hooks.partition_point(|(p, _)| *p >= priority);
+        self.after_build_hooks.insert(at, (priority, hook));
+    }
+    /// Registers the dependencies requested by a hook now that the requesting service is stored,
+    /// so a dependency requesting it back is skipped instead of recursing.
+    fn register_requested(&mut self) -> Result<(),RsServiceError> {
+        for register_requested in core::mem::take(&mut self.requested_services) {
+            register_requested(self)?;
+        }
+        Ok(())
+    }
+    /// Runs the `on_service_created` hook of an instance and stores it.
+    fn store_service<T>(&mut self, instance: T) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        if let Err(e) = self.insert_service(instance) {
+            // Drop what the failed hook requested, so the next registration doesn't pick it up.
+            self.requested_services.clear();
             return Err(e);
         }
+        self.register_requested()
+    }
+    /// Runs the `on_service_created` hook of an instance and stores it with its after-build hook,
+    /// leaving the registrations the hook requested to `register_requested`.
+    fn insert_service<T>(&mut self, mut instance: T) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        let type_id = TypeId::of::<T>();
+        self.run_service_created(&mut instance)?;
         let service_arc_mutex: Arc<Mutex<T>> = Arc::new(Mutex::new(instance));
 
         // Store the Arc<Mutex<T>> itself, but boxed and type-erased.
+        self.init_order.push(core::any::type_name::<T>());
         self.pending_services.insert(
             type_id,
-            Box::new(service_arc_mutex.clone()) as ContainerStruct,
+            ServiceContainer {
+                container: Box::new(service_arc_mutex.clone()) as ContainerStruct,
+                metadata: metadata_of::<T>(),
+                health_check: Some(check_health::<T>),
+            },
         );
         
         // Example: Preparing an after_build hook for this service T
         // This specific hook implementation would require T to implement on_all_services_built
-        self.after_build_hooks.push(Box::new(move |ctx: &RSContext| {
+        let hook: AfterBuildHook = Box::new(move |ctx: &RSContext, recover_poisoned: bool, catch_panics: bool| {
             if let Some(service_access) = ctx.call::<T>() { // Using call to get the Arc<Mutex<T>>
-                let service_guard = service_access.lock().map_err(|_| RsServiceError("Mutex poisoned".to_string()))?;
-                service_guard.on_all_services_built(ctx)?;
+                let service_guard = lock_service(&service_access, recover_poisoned)?;
+                traced("on_all_services_built", T::name(), || {
+                    run_hook::<T, _>(catch_panics, "on_all_services_built", || service_guard.on_all_services_built(ctx))
+                })?;
             }
             Ok(())
-        }));
+        });
+        self.push_after_build_hook::<T>(hook);
+
+        Ok(())
+    }
+    /// Runs the `on_service_created` hook of an instance and stores it as a plain `Arc<T>`.
+    fn store_shared_service<T>(&mut self, mut instance: T) -> Result<(),RsServiceError>
+    where
+        T: RSContextService,
+    {
+        if let Err(e) = self.run_service_created(&mut instance) {
+            self.requested_services.clear();
+            return Err(e);
+        }
+        self.init_order.push(core::any::type_name::<T>());
+        self.pending_services.insert(
+            TypeId::of::<T>(),
+            ServiceContainer {
+                container: Box::new(Arc::new(instance)) as ContainerStruct,
+                metadata: metadata_of::<T>(),
+                health_check: Some(check_health::<T>),
+            },
+        );
+
+        let hook: AfterBuildHook = Box::new(move |ctx: &RSContext, _recover_poisoned: bool, catch_panics: bool| {
+            if let Some(service) = ctx.call_shared::<T>() {
+                traced("on_all_services_built", T::name(), || {
+                    run_hook::<T, _>(catch_panics, "on_all_services_built", || service.on_all_services_built(ctx))
+                })?;
+            }
+            Ok(())
+        });
+        self.push_after_build_hook::<T>(hook);
 
+        self.register_requested()
+    }
+    /// Requests registration of service type T from inside an `on_service_created` hook.
+    /// T is registered right after the requesting service, unless it is already registered.
+    pub fn request_register<T>(&mut self)
+    where
+        T: RSContextService,
+    {
+        self.requested_services.push(Box::new(|builder: &mut RSContextBuilder| {
+            if builder.is_registered(TypeId::of::<T>()) {
+                Ok(())
+            } else {
+                builder.try_register::<T>()
+            }
+        }));
+    }
+    /// Records a non-fatal startup concern, e.g. from `on_service_created` when a config value
+    /// falls back to a default. `build_with_warnings` returns them; `build` drops them.
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.warnings.push(message.into());
+    }
+    /// Retrieves a service that is already stored in this builder (or in a parent context),
+    /// so an `on_service_created` hook can wire in a peer registered before it.
+    /// Deferred and lazy services have no instance yet, so they are not returned.
+    pub fn call_pending<T>(&self) -> Option<Arc<Mutex<T>>>
+    where
+        T: RSContextService,
+    {
+        let type_id = TypeId::of::<T>();
+        self.pending_services
+            .get(&type_id)
+            .or_else(|| self.parent_maps.iter().find_map(|parent| parent.get(&type_id)))?
+            .container
+            .downcast_ref::<Arc<Mutex<T>>>()
+            .cloned()
+    }
+    /// Registers a trait object under the trait's own TypeId, e.g. `register_dyn::<dyn Greeter + Send>(Box::new(English))`,
+    /// so callers retrieve it with `RSContext::call_dyn` without naming the concrete type.
+    /// It is not an `RSContextService`, so no hooks run for it.
+    pub fn register_dyn<Dyn>(mut self, instance: Box<Dyn>) -> Result<Self,RsServiceError>
+    where
+        Dyn: ?Sized + Send + 'static,
+    {
+        self.ensure_unregistered::<Dyn>()?;
+        let type_id = TypeId::of::<Dyn>();
+        self.pending_services.insert(
+            type_id,
+            ServiceContainer {
+                container: Box::new(Arc::new(Mutex::new(instance))) as ContainerStruct,
+                metadata: ServiceMetadata::of::<Dyn>(),
+                health_check: None,
+            },
+        );
         Ok(self)
     }
+    /// Registers a service type T whose instance is created on the first `call::<T>()`.
+    /// `on_service_created` is not called for lazy services since the builder no longer exists,
+    /// and `on_all_services_built` runs once, when the instance is created, with this builder's
+    /// `catch_panics` and `map_hook_errors` settings. If it fails, `RSContext::try_call` returns the error.
+    pub fn register_lazy<T>(mut self) -> Result<Self,RsServiceError>
+    where
+        T: RSContextService,
+    {
+        self.ensure_unregistered::<T>()?;
+        self.record_category_type::<T>();
+        let type_id = TypeId::of::<T>();
+        let lazy_service: LazyService<T> = LazyService { cell: OnceLock::new(), settings: Arc::clone(&self.hook_settings) };
+        self.pending_services.insert(
+            type_id,
+            ServiceContainer {
+                container: Box::new(lazy_service) as ContainerStruct,
+                metadata: metadata_of::<T>(),
+                health_check: Some(check_health::<T>),
+            },
+        );
+        Ok(self)
+    }
+    /// Makes the after-build hooks recover a poisoned service mutex via `PoisonError::into_inner`
+    /// instead of failing the whole build with `RsServiceError::LockPoisoned`.
+    /// Has no effect with the `spin` or `parking_lot` features, whose mutexes are never poisoned.
+    ///
+    /// A mutex is poisoned when a thread panicked while holding it, so the recovered service
+    /// may be in a partially-updated state. Only enable this for services whose state stays
+    /// valid (or is re-validated in `on_all_services_built`) after an interrupted update.
+    pub fn recover_poisoned(mut self, recover: bool) -> Self {
+        self.recover_poisoned = recover;
+        self
+    }
+    /// Sets a mapper that every hook failure (`RsServiceError::HookFailed`) passes through
+    /// before the builder returns it, along with the TypeId of the failing service.
+    /// Useful to annotate all hook errors in one place, e.g. with a correlation id.
+    pub fn map_hook_errors(mut self, f: impl Fn(TypeId, RsServiceError) -> RsServiceError + Send + Sync + 'static) -> Self {
+        self.hook_error_mapper = Some(Arc::new(f));
+        self
+    }
+    /// Makes `build` fail with `RsServiceError::Other("no services registered")` if nothing was registered,
+    /// which is almost always a configuration mistake. Off by default.
+    pub fn require_non_empty(mut self) -> Self {
+        self.require_non_empty = true;
+        self
+    }
+    /// Whether a panic in a hook (`on_register_crate_instance` or a `register_with` factory,
+    /// `on_service_created`, `on_all_services_built` or `health_check`) is caught and returned
+    /// as `RsServiceError::HookFailed` naming the service (the default), or left to unwind.
+    /// Set it before registering services, since services are created during registration.
+    /// Has no effect with the `spin` feature, where panics can't be caught.
+    pub fn catch_panics(mut self, catch: bool) -> Self {
+        self.catch_panics = catch;
+        self
+    }
+    /// Sets the category carried by the built context, readable with `RSContext::get_category`.
     pub fn set_category<TC>(mut self, _category: TC) -> Result<Self, RsServiceError>
     where
         TC: Any + Send + Sync + 'static, // Ensure TC is a type that can be boxed
     {
         self.category_info = Box::new(_category);
+        self.category_name = core::any::type_name::<TC>();
+        self.category_to_json = None;
+        Ok(self)
+    }
+    /// Registers a plain function or closure under `name`, for behaviour that doesn't need
+    /// a service struct (e.g. a request handler). Retrieve it with `RSContext::call_fn::<A, R>(name)`.
+    /// Fails with `AlreadyRegistered` if a function with that name is registered already.
+    pub fn register_from_fn<A, R, F>(mut self, name: &str, f: F) -> Result<Self, RsServiceError>
+    where
+        A: 'static,
+        R: 'static,
+        F: Fn(A) -> R + Send + Sync + 'static,
+    {
+        if self.functions.contains_key(name) {
+            return Err(RsServiceError::AlreadyRegistered(name.to_string()));
+        }
+        let function: SharedFn<A, R> = Arc::new(f);
+        self.functions.insert(name.to_string(), Box::new(function));
         Ok(self)
     }
+    /// Runs `f` on the category if it is a `TC`, e.g. so services can add to a shared config
+    /// from `on_service_created`. Returns what `f` returns, or None if the category isn't a `TC`.
+    pub fn with_category_mut<TC, R>(&mut self, f: impl FnOnce(&mut TC) -> R) -> Option<R>
+    where
+        TC: Any,
+    {
+        self.category_info.downcast_mut::<TC>().map(f)
+    }
+    /// Sets the category by deserializing `json` into `TC`, so `RSContext::category_to_json`
+    /// can serialize it back later. Fails with `RsServiceError::Other` if `json` isn't a valid `TC`.
+    #[cfg(feature = "serde")]
+    pub fn set_category_json<TC>(self, json: &str) -> Result<Self, RsServiceError>
+    where
+        TC: serde::Serialize + serde::de::DeserializeOwned + Any + Send + Sync + 'static,
+    {
+        let category: TC = serde_json::from_str(json)
+            .map_err(|e| RsServiceError::Other(format!("invalid category JSON: {e}")))?;
+        let mut builder = self.set_category(category)?;
+        builder.category_to_json = Some(category_to_json::<TC>);
+        Ok(builder)
+    }
+    /// Creates the deferred services and the context, returning the after-build hooks unrun.
+    fn into_parts(mut self) -> Result<(RSContext, PostBuildHooks), RsServiceError> {
+        // Create the deferred services whose dependencies were registered lazily.
+        self.register_ready_deferred()?;
+        let builder = self;
+        builder.check_deferred()?;
+        builder.check_non_empty()?;
+        let context = RSContext {
+            service_map: Arc::new(builder.pending_services), // Move the map
+            parent_maps: builder.parent_maps,
+            category: Arc::from(builder.category_info),
+            category_name: builder.category_name,
+            category_to_json: builder.category_to_json,
+            functions: Arc::new(builder.functions),
+            post_build_hooks: Arc::new(Mutex::new(None)),
+            hook_settings: Arc::clone(&builder.hook_settings),
+        };
+        let settings = HookSettings {
+            catch_panics: builder.catch_panics,
+            hook_error_mapper: builder.hook_error_mapper.clone(),
+        };
+        #[cfg(not(feature = "spin"))]
+        let _ = builder.hook_settings.set(settings);
+        #[cfg(feature = "spin")]
+        builder.hook_settings.call_once(|| settings);
+        let hooks = PostBuildHooks {
+            hooks: builder.after_build_hooks,
+            recover_poisoned: builder.recover_poisoned,
+            catch_panics: builder.catch_panics,
+            hook_error_mapper: builder.hook_error_mapper,
+        };
+        Ok((context, hooks))
+    }
     /// Builds the RSContext from the registered services.
     /// and calls the on_all_services_built hooks.
     pub fn build(self) -> Result<RSContext, RsServiceError> { // Return Result for better error handling
-        let context = RSContext {
-            service_map: self.pending_services, // Move the map
-            category: self.category_info,
-        };
-
-        // Call after_build hooks
-        for hook_fn in self.after_build_hooks {
-            hook_fn(&context)?;
-        }
-
-        Ok(context)
+        traced("build", core::any::type_name::<RSContext>(), || {
+            let (context, hooks) = self.into_parts()?;
+            hooks.run(&context)?;
+            Ok(context)
+        })
+    }
+    /// Builds the RSContext like `build`, but leaves the `on_all_services_built` hooks
+    /// to `RSContext::run_post_build_hooks`, so the context can be inspected first.
+    /// Services are usable meanwhile, but haven't seen their `on_all_services_built` yet.
+    pub fn build_without_hooks(self) -> Result<RSContext, RsServiceError> {
+        traced("build", core::any::type_name::<RSContext>(), || {
+            let (mut context, hooks) = self.into_parts()?;
+            context.post_build_hooks = Arc::new(Mutex::new(Some(hooks)));
+            Ok(context)
+        })
+    }
+    /// Builds the RSContext like `build`, also returning the warnings hooks reported with `warn`
+    /// during registration, e.g. to log them once startup has finished.
+    pub fn build_with_warnings(self) -> Result<(RSContext, Vec<String>), RsServiceError> {
+        // Deferred services are created first so the warnings of their hooks are included.
+        let mut builder = self;
+        builder.register_ready_deferred()?;
+        let warnings = core::mem::take(&mut builder.warnings);
+        let context = builder.build()?;
+        Ok((context, warnings))
+    }
+    /// Builds the RSContext behind an Arc, ready to be shared with other threads.
+    /// The after-build hooks run against the same context that is returned.
+    pub fn build_arc(self) -> Result<Arc<RSContext>, RsServiceError> {
+        traced("build", core::any::type_name::<RSContext>(), || {
+            let (context, hooks) = self.into_parts()?;
+            let context = Arc::new(context);
+            hooks.run(&context)?;
+            Ok(context)
+        })
     }
 }
\ No newline at end of file
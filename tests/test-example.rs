@@ -0,0 +1,2884 @@
+use std::{any::{type_name, TypeId}, error::Error, sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc}};
+
+use rs_ervice::{impl_rs_service, RSContext, RSContextBuilder, RSContextService, Registration, RsServiceError};
+use rs_ervice_macro_lib::{r_service, r_service_struct, RSContextService};
+
+// std mutexes return a poisoning Result; spin and parking_lot hand out the guard directly.
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+fn lock<T: ?Sized>(service: &rs_ervice::ServiceMutex<T>) -> impl std::ops::DerefMut<Target = T> + '_ {
+    #[cfg(not(any(feature = "spin", feature = "parking_lot")))]
+    return service.lock().unwrap();
+    #[cfg(any(feature = "spin", feature = "parking_lot"))]
+    return service.lock();
+}
+
+#[r_service_struct]
+struct CounterService {}
+
+#[r_service(auto_impl)]
+impl CounterService {
+    pub fn new() -> Self {
+        CounterService {}
+    }
+}
+
+// The hint only reserves room for hooks; registering more services than hinted still works.
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn with_service_hint_builds_like_new() {
+    let context = RSContextBuilder::with_service_hint(1)
+        .register::<CounterService>()
+        .and_then(|builder| builder.register::<DerivedService>())
+        .and_then(|builder| builder.register::<TrafficLight>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+    assert_eq!(context.len(), 3);
+    assert!(RSContextBuilder::with_service_hint(0).build().unwrap().is_empty());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn with_service_hint_builds_like_new() {
+    let context = RSContextBuilder::with_service_hint(1)
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .register::<DerivedService>()
+        .await
+        .unwrap()
+        .register::<TrafficLight>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(context.len(), 3);
+    assert!(RSContextBuilder::with_service_hint(0).build().await.unwrap().is_empty());
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn register_twice_is_already_registered() {
+    let result = RSContextBuilder::new()
+        .register::<CounterService>()
+        .and_then(|builder| builder.register::<CounterService>());
+
+    assert!(matches!(result, Err(RsServiceError::AlreadyRegistered(_))));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn register_twice_is_already_registered() {
+    let result = RSContextBuilder::new()
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .register::<CounterService>()
+        .await;
+
+    assert!(matches!(result, Err(RsServiceError::AlreadyRegistered(_))));
+}
+
+#[r_service_struct]
+struct FailingService {}
+
+impl_rs_service! {
+    impl FailingService {
+        fn on_register_crate_instance() -> Self {
+            FailingService {}
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Err(RsServiceError::Other("boom".to_string()))
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+    }
+}
+
+fn assert_hook_failure_keeps_source(result: Result<RSContextBuilder, RsServiceError>) {
+    let err = result.err().expect("FailingService should fail to register");
+    assert!(matches!(
+        err,
+        RsServiceError::HookFailed { hook: "on_service_created", type_id, .. } if type_id == TypeId::of::<FailingService>()
+    ));
+    let source = err.source().expect("HookFailed should expose its source");
+    assert_eq!(source.to_string(), "RsService Error: boom");
+
+    let cloned = err.clone();
+    assert_eq!(cloned.to_string(), err.to_string());
+    assert_eq!(cloned.source().unwrap().to_string(), "RsService Error: boom");
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn hook_failure_exposes_source() {
+    assert_hook_failure_keeps_source(RSContextBuilder::new().register::<FailingService>());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn hook_failure_exposes_source() {
+    assert_hook_failure_keeps_source(RSContextBuilder::new().register::<FailingService>().await);
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn try_register_keeps_the_builder_on_error() {
+    let mut builder = RSContextBuilder::new();
+    builder.try_register::<CounterService>().unwrap();
+    assert!(builder.try_register::<FailingService>().is_err());
+    assert!(matches!(builder.try_register::<CounterService>(), Err(RsServiceError::AlreadyRegistered(_))));
+
+    let context = builder.build().unwrap();
+    assert!(context.call::<CounterService>().is_some());
+    assert!(context.call::<FailingService>().is_none());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn try_register_keeps_the_builder_on_error() {
+    let mut builder = RSContextBuilder::new();
+    builder.try_register::<CounterService>().await.unwrap();
+    assert!(builder.try_register::<FailingService>().await.is_err());
+    assert!(matches!(builder.try_register::<CounterService>().await, Err(RsServiceError::AlreadyRegistered(_))));
+
+    let context = builder.build().await.unwrap();
+    assert!(context.call::<CounterService>().is_some());
+    assert!(context.call::<FailingService>().is_none());
+}
+
+/// Requests FailingService from its hook, so its registration fails after it was stored.
+#[r_service_struct]
+struct RequestsFailingService {}
+
+impl_rs_service! {
+    impl RequestsFailingService {
+        fn on_register_crate_instance() -> Self {
+            RequestsFailingService {}
+        }
+        fn on_service_created(&mut self, builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            builder.request_register::<FailingService>();
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn try_register_keeps_earlier_services_when_a_requested_service_fails() {
+    let mut builder = RSContextBuilder::new().set_category(8080u16).unwrap();
+    builder.try_register::<CounterService>().unwrap();
+    assert!(builder.try_register::<RequestsFailingService>().is_err());
+
+    let context = builder.build().unwrap();
+    assert!(context.call::<CounterService>().is_some());
+    assert!(context.call::<RequestsFailingService>().is_some());
+    assert!(context.call::<FailingService>().is_none());
+    assert_eq!(context.get_category::<u16>(), Some(&8080));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn try_register_keeps_earlier_services_when_a_requested_service_fails() {
+    let mut builder = RSContextBuilder::new().set_category(8080u16).unwrap();
+    builder.try_register::<CounterService>().await.unwrap();
+    assert!(builder.try_register::<RequestsFailingService>().await.is_err());
+
+    let context = builder.build().await.unwrap();
+    assert!(context.call::<CounterService>().is_some());
+    assert!(context.call::<RequestsFailingService>().is_some());
+    assert!(context.call::<FailingService>().is_none());
+    assert_eq!(context.get_category::<u16>(), Some(&8080));
+}
+
+// Plain #[test] on every backend: `with_service` needs no runtime.
+#[test]
+fn with_service_skips_hooks() {
+    let context = RSContext::with_service(FailingService {});
+
+    assert!(context.call::<FailingService>().is_some());
+    assert_eq!(context.len(), 1);
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn test_context_builder_skips_hooks() {
+    let context = rs_ervice::TestContextBuilder::new()
+        .with(FailingService {})
+        .with(DerivedService { port: 80 })
+        .with(DerivedService { port: 443 })
+        .finish();
+
+    assert!(context.call::<FailingService>().is_some());
+    assert!(context.call::<CounterService>().is_none());
+    assert_eq!(context.len(), 2);
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn call_or_default_falls_back_to_an_unshared_default() {
+    let context = RSContext::default();
+
+    assert!(!Arc::ptr_eq(&context.call_or_default::<DerivedService>(), &context.call_or_default::<DerivedService>()));
+    assert!(context.call::<DerivedService>().is_none());
+
+    let context = RSContext::with_service(DerivedService { port: 80 });
+    assert!(Arc::ptr_eq(&context.call_or_default::<DerivedService>(), &context.call::<DerivedService>().unwrap()));
+
+    // A lazy service that wasn't created yet is created, not replaced by a default.
+    let context = RSContextBuilder::new().register_lazy::<DerivedService>().unwrap().build().unwrap();
+    assert!(Arc::ptr_eq(&context.call_or_default::<DerivedService>(), &context.call::<DerivedService>().unwrap()));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn call_or_default_falls_back_to_an_unshared_default() {
+    let context = RSContext::default();
+
+    assert!(!Arc::ptr_eq(&context.call_or_default::<DerivedService>().await, &context.call_or_default::<DerivedService>().await));
+    assert!(context.call::<DerivedService>().is_none());
+
+    let context = RSContext::with_service(DerivedService { port: 80 });
+    assert!(Arc::ptr_eq(&context.call_or_default::<DerivedService>().await, &context.call::<DerivedService>().unwrap()));
+
+    // A lazy service that wasn't created yet is created, not replaced by a default.
+    let context = RSContextBuilder::new().register_lazy::<DerivedService>().unwrap().build().await.unwrap();
+    assert!(context.call::<DerivedService>().is_none());
+    let service = context.call_or_default::<DerivedService>().await;
+    assert!(Arc::ptr_eq(&service, &context.call::<DerivedService>().unwrap()));
+}
+
+#[test]
+fn call_weak_does_not_keep_the_service_alive() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+    let weak = context.call_weak::<DerivedService>().unwrap();
+
+    assert!(weak.upgrade().is_some());
+    drop(context);
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn frozen_context_only_looks_services_up() {
+    let frozen = RSContext::with_service(DerivedService { port: 80 }).freeze();
+
+    assert!(frozen.contains::<DerivedService>());
+    assert!(!frozen.contains::<CounterService>());
+    assert!(frozen.call::<DerivedService>().is_some());
+    assert_eq!(frozen.get_metadata().len(), 1);
+    assert_eq!(frozen.unfreeze().len(), 1);
+}
+
+#[test]
+fn context_iterates_over_metadata() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+
+    let mut seen = Vec::new();
+    for service in &context {
+        seen.push(service.type_id);
+    }
+    assert_eq!(seen, vec![TypeId::of::<DerivedService>()]);
+}
+
+#[test]
+fn call_with_borrows_the_stored_arc() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+
+    assert_eq!(context.call_with::<DerivedService, _>(Arc::strong_count), Some(1));
+    assert!(context.call_with::<CounterService, _>(Arc::strong_count).is_none());
+}
+
+// The std error conversions aren't available in the no_std spin build.
+#[cfg(not(feature = "spin"))]
+#[test]
+fn common_errors_convert_into_rs_service_error() {
+    let io: RsServiceError = std::io::Error::new(std::io::ErrorKind::NotFound, "config.toml").into();
+    assert_eq!(io.to_string(), "RsService Error: I/O error: config.toml");
+    let source = io.source().expect("the I/O error is kept as the source");
+    assert_eq!(source.downcast_ref::<std::io::Error>().map(std::io::Error::kind), Some(std::io::ErrorKind::NotFound));
+
+    let boxed: Box<dyn Error> = "bad config".into();
+    assert!(matches!(RsServiceError::from(boxed), RsServiceError::Other(message) if message == "bad config"));
+
+    let boxed: Box<dyn Error + Send + Sync> = Box::new(std::io::Error::other("bad config"));
+    let error = RsServiceError::from(boxed);
+    assert_eq!(error.to_string(), "RsService Error: external error: bad config");
+    assert!(error.source().is_some_and(|source| source.is::<std::io::Error>()));
+
+    let mutex = std::sync::Mutex::new(0);
+    let _ = std::panic::catch_unwind(|| {
+        let _guard = mutex.lock().unwrap();
+        panic!("poison");
+    });
+    let poisoned: RsServiceError = mutex.lock().unwrap_err().into();
+    assert!(matches!(poisoned, RsServiceError::LockPoisoned(guard) if guard.contains("MutexGuard")));
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn replace_service_is_seen_through_earlier_arcs() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+    let held = context.call::<DerivedService>().unwrap();
+
+    context.replace_service(DerivedService { port: 443 }).unwrap();
+    assert_eq!(lock(&held).port, 443);
+    assert!(Arc::ptr_eq(&held, &context.call::<DerivedService>().unwrap()));
+    assert!(matches!(context.replace_service(TrafficLight::new()), Err(RsServiceError::NotFound(_))));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn replace_service_is_seen_through_earlier_arcs() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+    let held = context.call::<DerivedService>().unwrap();
+
+    context.replace_service(DerivedService { port: 443 }).await.unwrap();
+    assert_eq!(held.lock().await.port, 443);
+    assert!(Arc::ptr_eq(&held, &context.call::<DerivedService>().unwrap()));
+    assert!(matches!(context.replace_service(TrafficLight::new()).await, Err(RsServiceError::NotFound(_))));
+}
+
+// Only std mutexes are poisoned; spin and parking_lot ones aren't.
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "spin", feature = "parking_lot")))]
+#[test]
+fn poisoned_services_fail_the_build_unless_recovered() {
+    let poison = |service: Arc<rs_ervice::ServiceMutex<CounterService>>| {
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _guard = service.lock().unwrap();
+            panic!("poison");
+        }));
+    };
+
+    let builder = RSContextBuilder::new().register::<CounterService>().unwrap();
+    poison(builder.call_pending::<CounterService>().unwrap());
+    assert!(matches!(
+        builder.build(),
+        Err(RsServiceError::LockPoisoned(name)) if name == type_name::<CounterService>()
+    ));
+
+    let builder = RSContextBuilder::new()
+        .recover_poisoned(true)
+        .register::<CounterService>()
+        .unwrap();
+    poison(builder.call_pending::<CounterService>().unwrap());
+    let context = builder.build().unwrap();
+    // Only the after-build hooks recover; later locks still report the poisoned mutex.
+    assert!(matches!(context.call_map::<CounterService, _>(|_| ()), Err(RsServiceError::LockPoisoned(_))));
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn call_owned_returns_a_snapshot() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+    // Another Arc clone stays alive; call_owned only locks long enough to clone.
+    let held = context.call::<DerivedService>().unwrap();
+
+    let owned = context.call_owned::<DerivedService>().unwrap();
+    lock(&held).port = 443;
+    assert_eq!(owned.port, 80);
+    assert_eq!(context.call_owned::<DerivedService>().unwrap().port, 443);
+    assert_eq!(Arc::strong_count(&held), 2);
+    assert!(context.call_owned::<DerivedWithNewService>().is_none());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn call_owned_returns_a_snapshot() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+    // Another Arc clone stays alive; call_owned only locks long enough to clone.
+    let held = context.call::<DerivedService>().unwrap();
+
+    let owned = context.call_owned::<DerivedService>().await.unwrap();
+    held.lock().await.port = 443;
+    assert_eq!(owned.port, 80);
+    assert_eq!(context.call_owned::<DerivedService>().await.unwrap().port, 443);
+    assert_eq!(Arc::strong_count(&held), 2);
+    assert!(context.call_owned::<DerivedWithNewService>().await.is_none());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn with_service_timeout_gives_up_on_a_held_lock() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+    let held = context.call::<DerivedService>().unwrap();
+    let timeout = std::time::Duration::from_millis(10);
+
+    let guard = held.lock().await;
+    let result = context.with_service_timeout::<DerivedService, _>(timeout, |service| service.port).await;
+    assert!(matches!(result, Err(RsServiceError::Timeout(message)) if message.contains("DerivedService")));
+
+    drop(guard);
+    assert_eq!(context.with_service_timeout::<DerivedService, _>(timeout, |service| service.port).await.unwrap(), 80);
+    assert!(matches!(
+        context.with_service_timeout::<TrafficLight, _>(timeout, |_| ()).await,
+        Err(RsServiceError::NotFound(_))
+    ));
+}
+
+// The mutex `RSContext::call` hands out on each backend.
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+type Guarded<T> = rs_ervice::ServiceMutex<T>;
+#[cfg(feature = "tokio")]
+type Guarded<T> = tokio::sync::Mutex<T>;
+#[cfg(all(feature = "async-std", not(feature = "tokio")))]
+type Guarded<T> = async_std::sync::Mutex<T>;
+
+#[test]
+fn iter_services_yields_each_stored_service() {
+    let context = RSContext::with_service(DerivedService { port: 80 })
+        .merge(RSContext::with_service(TrafficLight::new()))
+        .unwrap();
+
+    let mut type_ids: Vec<TypeId> = context.iter_services().map(|(type_id, _)| type_id).collect();
+    type_ids.sort();
+    let mut expected = vec![TypeId::of::<DerivedService>(), TypeId::of::<TrafficLight>()];
+    expected.sort();
+    assert_eq!(type_ids, expected);
+
+    // Each container is the same Arc that `call` returns.
+    let (_, container) = context.iter_services()
+        .find(|(type_id, _)| *type_id == TypeId::of::<DerivedService>())
+        .unwrap();
+    let service = container.downcast_ref::<Arc<Guarded<DerivedService>>>().unwrap();
+    assert!(Arc::ptr_eq(service, &context.call::<DerivedService>().unwrap()));
+}
+
+#[test]
+fn take_service_unregisters_it() {
+    let mut context = RSContext::with_service(DerivedService { port: 80 });
+    let held = context.call::<DerivedService>().unwrap();
+
+    let taken = context.take_service::<DerivedService>().unwrap();
+    assert!(Arc::ptr_eq(&held, &taken));
+    assert!(context.call::<DerivedService>().is_none());
+    assert!(context.take_service::<DerivedService>().is_none());
+}
+
+#[test]
+fn clones_share_the_same_services() {
+    let mut context = RSContext::with_service(DerivedService { port: 80 });
+    let clone = context.clone();
+    assert!(Arc::ptr_eq(&context.call::<DerivedService>().unwrap(), &clone.call::<DerivedService>().unwrap()));
+
+    // The service map is shared, so it can't be taken while a clone is alive.
+    assert!(context.take_service::<DerivedService>().is_none());
+    drop(clone);
+    assert!(context.take_service::<DerivedService>().is_some());
+}
+
+#[test]
+fn debug_lists_service_type_names() {
+    let printed = format!("{:?}", RSContext::with_service(DerivedService { port: 80 }));
+    assert!(printed.contains("DerivedService"));
+    assert!(printed.contains(r#"category: "()""#));
+}
+
+#[test]
+fn service_type_names_lists_registered_services() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+    assert_eq!(context.service_type_names(), vec![std::any::type_name::<DerivedService>()]);
+}
+
+#[r_service_struct]
+struct PluginService {}
+
+impl_rs_service! {
+    impl PluginService {
+        fn on_register_crate_instance() -> Self {
+            PluginService {}
+        }
+        fn on_service_created(&mut self, builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            builder.request_register::<CounterService>();
+            builder.request_register::<PluginService>();
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn hook_can_request_dependencies() {
+    let context = RSContextBuilder::new()
+        .register::<PluginService>()
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    assert!(context.call::<CounterService>().is_some());
+    assert_eq!(context.get_metadata().len(), 2);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn hook_can_request_dependencies() {
+    let context = RSContextBuilder::new()
+        .register::<PluginService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    assert!(context.call::<CounterService>().is_some());
+    assert_eq!(context.get_metadata().len(), 2);
+}
+
+#[r_service_struct]
+struct LegacyConfigService {}
+
+impl_rs_service! {
+    impl LegacyConfigService {
+        fn on_register_crate_instance() -> Self {
+            LegacyConfigService {}
+        }
+        fn on_service_created(&mut self, builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            builder.warn("LEGACY_PORT is deprecated, using the default port");
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn build_with_warnings_returns_hook_warnings() {
+    let (context, warnings) = RSContextBuilder::new()
+        .register::<LegacyConfigService>()
+        .unwrap()
+        .build_with_warnings()
+        .unwrap();
+
+    assert!(context.call::<LegacyConfigService>().is_some());
+    assert_eq!(warnings, vec!["LEGACY_PORT is deprecated, using the default port".to_string()]);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn build_with_warnings_returns_hook_warnings() {
+    let (context, warnings) = RSContextBuilder::new()
+        .register::<LegacyConfigService>()
+        .await
+        .unwrap()
+        .build_with_warnings()
+        .await
+        .unwrap();
+
+    assert!(context.call::<LegacyConfigService>().is_some());
+    assert_eq!(warnings, vec!["LEGACY_PORT is deprecated, using the default port".to_string()]);
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn child_context_falls_through_to_parent() {
+    let parent = RSContextBuilder::new()
+        .register::<CounterService>()
+        .and_then(|builder| builder.build())
+        .unwrap();
+    let child = parent.child().build().unwrap();
+
+    assert!(child.call::<CounterService>().is_some());
+    assert!(child.is_empty());
+    assert_eq!(parent.len(), 1);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn child_context_falls_through_to_parent() {
+    let parent = RSContextBuilder::new()
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let child = parent.child().build().await.unwrap();
+
+    assert!(child.call::<CounterService>().is_some());
+    assert!(child.is_empty());
+    assert_eq!(parent.len(), 1);
+}
+
+// Plain #[test] on every backend: `with_service` and `merge` need no runtime.
+#[test]
+fn merge_combines_services_and_rejects_collisions() {
+    let merged = RSContext::with_service(TrafficLight::new())
+        .merge(RSContext::with_service(DerivedService { port: 80 }))
+        .unwrap();
+    assert_eq!(merged.len(), 2);
+    assert!(merged.call::<TrafficLight>().is_some());
+    assert!(merged.call::<DerivedService>().is_some());
+
+    let duplicate = RSContext::with_service(DerivedService { port: 80 })
+        .merge(RSContext::with_service(DerivedService { port: 443 }));
+    assert!(matches!(duplicate, Err(RsServiceError::AlreadyRegistered(name)) if name == type_name::<DerivedService>()));
+
+    // A clone still shares the services, so they can't be moved into the merged context.
+    let context = RSContext::with_service(TrafficLight::new());
+    let _clone = context.clone();
+    assert!(matches!(context.merge(RSContext::with_service(DerivedService { port: 80 })), Err(RsServiceError::Other(_))));
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn merge_keeps_parent_maps_and_one_category() {
+    let parent = RSContextBuilder::new()
+        .register::<CounterService>()
+        .and_then(|builder| builder.build())
+        .unwrap();
+    let child = parent.child().build().unwrap();
+
+    // The merged context still falls through to the child's parent.
+    let merged = child.merge(RSContext::with_service(DerivedService { port: 80 })).unwrap();
+    assert_eq!(merged.len(), 1);
+    assert!(merged.call::<CounterService>().is_some());
+    assert!(merged.call::<DerivedService>().is_some());
+    // The parent's services are shared with the child's parent maps.
+    assert!(matches!(parent.merge(RSContext::with_service(TrafficLight::new())), Err(RsServiceError::Other(_))));
+
+    let categorized = RSContextBuilder::new().set_category(1u8).and_then(|builder| builder.build()).unwrap();
+    let merged = categorized.merge(RSContext::with_service(TrafficLight::new())).unwrap();
+    assert_eq!(merged.get_category::<u8>(), Some(&1));
+    let other = RSContextBuilder::new().set_category(2u8).and_then(|builder| builder.build()).unwrap();
+    assert!(matches!(merged.merge(other), Err(RsServiceError::Other(_))));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn merge_keeps_parent_maps_and_one_category() {
+    let parent = RSContextBuilder::new()
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let child = parent.child().build().await.unwrap();
+
+    // The merged context still falls through to the child's parent.
+    let merged = child.merge(RSContext::with_service(DerivedService { port: 80 })).unwrap();
+    assert_eq!(merged.len(), 1);
+    assert!(merged.call::<CounterService>().is_some());
+    assert!(merged.call::<DerivedService>().is_some());
+    // The parent's services are shared with the child's parent maps.
+    assert!(matches!(parent.merge(RSContext::with_service(TrafficLight::new())), Err(RsServiceError::Other(_))));
+
+    let categorized = RSContextBuilder::new().set_category(1u8).unwrap().build().await.unwrap();
+    let merged = categorized.merge(RSContext::with_service(TrafficLight::new())).unwrap();
+    assert_eq!(merged.get_category::<u8>(), Some(&1));
+    let other = RSContextBuilder::new().set_category(2u8).unwrap().build().await.unwrap();
+    assert!(matches!(merged.merge(other), Err(RsServiceError::Other(_))));
+}
+
+#[r_service_struct]
+struct DependentService {}
+
+impl_rs_service! {
+    impl DependentService {
+        fn on_register_crate_instance() -> Self {
+            DependentService {}
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn dependencies() -> Vec<TypeId> {
+            vec![TypeId::of::<CounterService>()]
+        }
+    }
+}
+
+#[r_service_struct]
+struct MacroDependentService {}
+
+#[r_service(auto_impl, depends_on = "CounterService, DerivedService")]
+impl MacroDependentService {
+    pub fn new() -> Self {
+        MacroDependentService {}
+    }
+}
+
+#[test]
+fn r_service_declares_dependencies() {
+    assert_eq!(
+        MacroDependentService::dependencies(),
+        vec![TypeId::of::<CounterService>(), TypeId::of::<DerivedService>()]
+    );
+    assert!(CounterService::dependencies().is_empty());
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn dependencies_are_resolved_before_build() {
+    let builder = RSContextBuilder::new()
+        .register::<DependentService>()
+        .unwrap();
+    assert_eq!(builder.validate().unwrap_err().len(), 1);
+    let missing = builder.build();
+    assert!(matches!(
+        missing,
+        Err(RsServiceError::MissingDependency { dependency, .. }) if dependency == TypeId::of::<CounterService>()
+    ));
+
+    let context = RSContextBuilder::new()
+        .register::<DependentService>()
+        .and_then(|builder| builder.register::<CounterService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+    assert!(context.call::<DependentService>().is_some());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn dependencies_are_resolved_before_build() {
+    let builder = RSContextBuilder::new()
+        .register::<DependentService>()
+        .await
+        .unwrap();
+    assert_eq!(builder.validate().unwrap_err().len(), 1);
+    let missing = builder.build().await;
+    assert!(matches!(
+        missing,
+        Err(RsServiceError::MissingDependency { dependency, .. }) if dependency == TypeId::of::<CounterService>()
+    ));
+
+    let context = RSContextBuilder::new()
+        .register::<DependentService>()
+        .await
+        .unwrap()
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert!(context.call::<DependentService>().is_some());
+}
+
+// Shares TrafficLight's logical name "light".
+#[derive(Default, RSContextService)]
+#[rservice(name = "light")]
+struct StreetLampService {}
+
+/// Reads a `u16` port from the category.
+#[r_service_struct]
+struct PortReaderService {}
+
+impl_rs_service! {
+    impl PortReaderService {
+        fn on_register_crate_instance() -> Self {
+            PortReaderService {}
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn category_type() -> Option<TypeId> {
+            Some(TypeId::of::<u16>())
+        }
+    }
+}
+
+fn assert_validate_errors(errors: Vec<RsServiceError>) {
+    assert_eq!(errors.len(), 3);
+    assert!(errors.iter().any(|e| matches!(e, RsServiceError::MissingDependency { .. })));
+    assert!(errors.iter().any(|e| matches!(e, RsServiceError::Other(message) if message.contains("\"light\""))));
+    assert!(errors.iter().any(|e| matches!(
+        e,
+        RsServiceError::CategoryMismatch { expected, found: "&str", .. } if *expected == TypeId::of::<u16>()
+    )));
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn validate_reports_every_problem() {
+    let builder = RSContextBuilder::new()
+        .set_category("not a port")
+        .and_then(|builder| builder.register::<TrafficLight>())
+        .and_then(|builder| builder.register::<StreetLampService>())
+        .and_then(|builder| builder.register::<DependentService>())
+        .and_then(|builder| builder.register::<PortReaderService>())
+        .unwrap();
+    assert_validate_errors(builder.validate().unwrap_err());
+
+    let builder = RSContextBuilder::new()
+        .set_category(8080u16)
+        .and_then(|builder| builder.register::<PortReaderService>())
+        .unwrap();
+    assert!(builder.validate().is_ok());
+
+    let errors = RSContextBuilder::new().require_non_empty().validate().unwrap_err();
+    assert!(matches!(&errors[..], [RsServiceError::Other(message)] if message == "no services registered"));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn validate_reports_every_problem() {
+    let builder = RSContextBuilder::new()
+        .set_category("not a port")
+        .unwrap()
+        .register::<TrafficLight>()
+        .await
+        .unwrap()
+        .register::<StreetLampService>()
+        .await
+        .unwrap()
+        .register::<DependentService>()
+        .await
+        .unwrap()
+        .register::<PortReaderService>()
+        .await
+        .unwrap();
+    assert_validate_errors(builder.validate().unwrap_err());
+
+    let builder = RSContextBuilder::new()
+        .set_category(8080u16)
+        .unwrap()
+        .register::<PortReaderService>()
+        .await
+        .unwrap();
+    assert!(builder.validate().is_ok());
+
+    let errors = RSContextBuilder::new().require_non_empty().validate().unwrap_err();
+    assert!(matches!(&errors[..], [RsServiceError::Other(message)] if message == "no services registered"));
+}
+
+#[r_service_struct(name = "light")]
+enum TrafficLight {
+    Red,
+}
+
+#[r_service(auto_impl)]
+impl TrafficLight {
+    pub fn new() -> Self {
+        TrafficLight::Red
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn enum_services_can_be_registered() {
+    let context = RSContextBuilder::new()
+        .register::<TrafficLight>()
+        .unwrap()
+        .build()
+        .unwrap();
+
+    assert_eq!(TrafficLight::RS_SERVICE_NAME, "light");
+    assert!(matches!(*lock(&context.call::<TrafficLight>().unwrap()), TrafficLight::Red));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn enum_services_can_be_registered() {
+    let context = RSContextBuilder::new()
+        .register::<TrafficLight>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(TrafficLight::RS_SERVICE_NAME, "light");
+    assert!(matches!(*context.call::<TrafficLight>().unwrap().lock().await, TrafficLight::Red));
+}
+
+mod billing {
+    use rs_ervice_macro_lib::r_service_struct;
+
+    #[r_service_struct]
+    pub struct InvoiceService {}
+}
+
+#[test]
+fn module_path_names_the_defining_module() {
+    assert_eq!(CounterService::RS_SERVICE_MODULE_PATH, module_path!());
+    assert_eq!(billing::InvoiceService::RS_SERVICE_MODULE_PATH, concat!(module_path!(), "::billing"));
+    assert_eq!(billing::InvoiceService::RS_SERVICE_MODULE_PATH, "test_example::billing");
+}
+
+// #[r_service(auto_impl)] on its own, without #[r_service_struct] on the type.
+struct ImplOnlyService;
+
+#[r_service(auto_impl)]
+impl ImplOnlyService {
+    pub fn new() -> Self {
+        ImplOnlyService
+    }
+}
+
+// Plain #[test] on every backend: `name()` and `with_service` need no runtime.
+#[test]
+fn service_names_come_from_the_macros() {
+    assert_eq!(<TrafficLight as RSContextService>::name(), "light");
+    assert_eq!(<DerivedWithNewService as RSContextService>::name(), "derived-with-new");
+    assert_eq!(<DerivedService as RSContextService>::name(), std::any::type_name::<DerivedService>());
+    assert_eq!(<ImplOnlyService as RSContextService>::name(), std::any::type_name::<ImplOnlyService>());
+
+    let context = RSContext::with_service(TrafficLight::new());
+    assert_eq!(context.get_metadata()[0].name, "light");
+
+    let context = RSContext::with_service(ImplOnlyService::new());
+    assert_eq!(context.get_metadata()[0].name, std::any::type_name::<ImplOnlyService>());
+}
+
+#[r_service_struct]
+struct PingService {
+    pongs: u32,
+}
+
+#[r_service_struct]
+struct PongService {
+    pings: u32,
+}
+
+// Written per backend: the async hooks `.await` the other service's lock.
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+impl RSContextService for PingService {
+    fn on_register_crate_instance() -> Self {
+        PingService { pongs: 0 }
+    }
+    fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+    fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+        lock(&context.call::<PongService>().unwrap()).pings += 1;
+        Ok(())
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+impl RSContextService for PongService {
+    fn on_register_crate_instance() -> Self {
+        PongService { pings: 0 }
+    }
+    fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+    fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+        lock(&context.call::<PingService>().unwrap()).pongs += 1;
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl RSContextService for PingService {
+    type Error = RsServiceError;
+    async fn on_register_crate_instance() -> Self {
+        PingService { pongs: 0 }
+    }
+    async fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+    async fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+        context.call::<PongService>().unwrap().lock().await.pings += 1;
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl RSContextService for PongService {
+    type Error = RsServiceError;
+    async fn on_register_crate_instance() -> Self {
+        PongService { pings: 0 }
+    }
+    async fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+    async fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+        context.call::<PingService>().unwrap().lock().await.pongs += 1;
+        Ok(())
+    }
+}
+
+// Each hook locks the other service; this only works because hooks never run concurrently.
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn hooks_can_lock_other_services() {
+    let context = RSContextBuilder::new()
+        .register::<PingService>()
+        .and_then(|builder| builder.register::<PongService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    assert_eq!(lock(&context.call::<PingService>().unwrap()).pongs, 1);
+    assert_eq!(lock(&context.call::<PongService>().unwrap()).pings, 1);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn hooks_can_lock_other_services() {
+    let context = RSContextBuilder::new()
+        .register::<PingService>()
+        .await
+        .unwrap()
+        .register::<PongService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(context.call::<PingService>().unwrap().lock().await.pongs, 1);
+    assert_eq!(context.call::<PongService>().unwrap().lock().await.pings, 1);
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn register_instance_keeps_the_given_value() {
+    let context = RSContextBuilder::new()
+        .register_instance(PingService { pongs: 41 })
+        .and_then(|builder| builder.register::<PongService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    assert_eq!(context.call_map(|ping: &mut PingService| ping.pongs).unwrap(), 42);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn register_instance_keeps_the_given_value() {
+    let context = RSContextBuilder::new()
+        .register_instance(PingService { pongs: 41 })
+        .await
+        .unwrap()
+        .register::<PongService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(context.call_map(|ping: &mut PingService| ping.pongs).await.unwrap(), 42);
+}
+
+#[r_service_struct]
+struct PeerAwareService {
+    saw_counter: bool,
+}
+
+impl_rs_service! {
+    impl PeerAwareService {
+        fn on_register_crate_instance() -> Self {
+            PeerAwareService { saw_counter: false }
+        }
+        fn on_service_created(&mut self, builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            self.saw_counter = builder.call_pending::<CounterService>().is_some();
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn call_pending_sees_earlier_services() {
+    let context = RSContextBuilder::new()
+        .register::<CounterService>()
+        .and_then(|builder| builder.register::<PeerAwareService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    assert!(lock(&context.call::<PeerAwareService>().unwrap()).saw_counter);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn call_pending_sees_earlier_services() {
+    let context = RSContextBuilder::new()
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .register::<PeerAwareService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    assert!(context.call::<PeerAwareService>().unwrap().lock().await.saw_counter);
+}
+
+#[r_service_struct]
+struct MetricsExporterService {
+    saw_peer: bool,
+}
+
+impl_rs_service! {
+    impl MetricsExporterService {
+        fn on_register_crate_instance() -> Self {
+            MetricsExporterService { saw_peer: false }
+        }
+        fn on_service_created(&mut self, builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            self.saw_peer = builder.call_pending::<DependentService>().is_some();
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn optional_dependencies() -> Vec<TypeId> {
+            vec![TypeId::of::<DependentService>()]
+        }
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn optional_dependencies_are_tolerated_and_waited_for() {
+    let alone = RSContextBuilder::new()
+        .register::<MetricsExporterService>()
+        .and_then(|builder| builder.build())
+        .unwrap();
+    assert!(!lock(&alone.call::<MetricsExporterService>().unwrap()).saw_peer);
+
+    // DependentService waits for CounterService, and the exporter waits for DependentService.
+    let context = RSContextBuilder::new()
+        .register::<DependentService>()
+        .and_then(|builder| builder.register::<MetricsExporterService>())
+        .and_then(|builder| builder.register::<CounterService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+    assert!(lock(&context.call::<MetricsExporterService>().unwrap()).saw_peer);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn optional_dependencies_are_tolerated_and_waited_for() {
+    let alone = RSContextBuilder::new()
+        .register::<MetricsExporterService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert!(!alone.call::<MetricsExporterService>().unwrap().lock().await.saw_peer);
+
+    // DependentService waits for CounterService, and the exporter waits for DependentService.
+    let context = RSContextBuilder::new()
+        .register::<DependentService>()
+        .await
+        .unwrap()
+        .register::<MetricsExporterService>()
+        .await
+        .unwrap()
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert!(context.call::<MetricsExporterService>().unwrap().lock().await.saw_peer);
+}
+
+// Optionally uses AuditSinkService, which in turn depends on it.
+#[r_service_struct]
+struct AuditService {
+    saw_sink: bool,
+}
+
+impl_rs_service! {
+    impl AuditService {
+        fn on_register_crate_instance() -> Self {
+            AuditService { saw_sink: false }
+        }
+        fn on_service_created(&mut self, builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            self.saw_sink = builder.call_pending::<AuditSinkService>().is_some();
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn optional_dependencies() -> Vec<TypeId> {
+            vec![TypeId::of::<AuditSinkService>()]
+        }
+    }
+}
+
+#[r_service_struct]
+struct AuditSinkService {}
+
+#[r_service(auto_impl, depends_on = "AuditService")]
+impl AuditSinkService {
+    pub fn new() -> Self {
+        AuditSinkService {}
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn optional_dependencies_waiting_for_the_dependent_are_not_waited_for() {
+    // AuditSinkService waits for AuditService, so AuditService can't wait for it in turn.
+    let context = RSContextBuilder::new()
+        .register::<AuditSinkService>()
+        .and_then(|builder| builder.register::<AuditService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+    assert!(!lock(&context.call::<AuditService>().unwrap()).saw_sink);
+    assert!(context.call::<AuditSinkService>().is_some());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn optional_dependencies_waiting_for_the_dependent_are_not_waited_for() {
+    // AuditSinkService waits for AuditService, so AuditService can't wait for it in turn.
+    let context = RSContextBuilder::new()
+        .register::<AuditSinkService>()
+        .await
+        .unwrap()
+        .register::<AuditService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert!(!context.call::<AuditService>().unwrap().lock().await.saw_sink);
+    assert!(context.call::<AuditSinkService>().is_some());
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn resolution_order_lists_created_then_deferred_services() {
+    let builder = RSContextBuilder::new()
+        .register::<DependentService>()
+        .and_then(|builder| builder.register::<MetricsExporterService>())
+        .unwrap();
+    assert!(builder.resolution_order().is_empty());
+
+    let builder = builder.register::<CounterService>().unwrap();
+    assert_eq!(builder.resolution_order(), vec![
+        type_name::<CounterService>(),
+        type_name::<DependentService>(),
+        type_name::<MetricsExporterService>(),
+    ]);
+
+    // A lazy service satisfies the dependency only at build, so DependentService is still deferred.
+    let builder = RSContextBuilder::new()
+        .register::<DependentService>()
+        .and_then(|builder| builder.register_lazy::<CounterService>())
+        .unwrap();
+    assert_eq!(builder.resolution_order(), vec![type_name::<DependentService>()]);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn resolution_order_lists_created_then_deferred_services() {
+    let builder = RSContextBuilder::new()
+        .register::<DependentService>()
+        .await
+        .unwrap()
+        .register::<MetricsExporterService>()
+        .await
+        .unwrap();
+    assert!(builder.resolution_order().is_empty());
+
+    let builder = builder.register::<CounterService>().await.unwrap();
+    assert_eq!(builder.resolution_order(), vec![
+        type_name::<CounterService>(),
+        type_name::<DependentService>(),
+        type_name::<MetricsExporterService>(),
+    ]);
+
+    // A lazy service satisfies the dependency only at build, so DependentService is still deferred.
+    let builder = RSContextBuilder::new()
+        .register::<DependentService>()
+        .await
+        .unwrap()
+        .register_lazy::<CounterService>()
+        .unwrap();
+    assert_eq!(builder.resolution_order(), vec![type_name::<DependentService>()]);
+}
+
+// Instances of LazyCountingService created, and its on_all_services_built hooks run.
+static LAZY_CREATED: AtomicUsize = AtomicUsize::new(0);
+static LAZY_BUILT: AtomicUsize = AtomicUsize::new(0);
+
+#[r_service_struct]
+struct LazyCountingService {}
+
+impl_rs_service! {
+    impl LazyCountingService {
+        fn on_register_crate_instance() -> Self {
+            LAZY_CREATED.fetch_add(1, Ordering::SeqCst);
+            LazyCountingService {}
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            LAZY_BUILT.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+}
+
+#[r_service_struct]
+struct LazyFailingService {}
+
+impl_rs_service! {
+    impl LazyFailingService {
+        fn on_register_crate_instance() -> Self {
+            LazyFailingService {}
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Err(RsServiceError::Other("lazy boom".to_string()))
+        }
+    }
+}
+
+fn assert_lazy_hook_failed<T>(result: Result<T, RsServiceError>) {
+    assert!(matches!(
+        result,
+        Err(RsServiceError::HookFailed { hook: "on_all_services_built", type_id, .. }) if type_id == TypeId::of::<LazyFailingService>()
+    ));
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn register_lazy_creates_the_service_once_on_first_use() {
+    let context = RSContextBuilder::new().register_lazy::<LazyCountingService>().unwrap().build().unwrap();
+    assert!(context.contains::<LazyCountingService>());
+    assert_eq!(LAZY_CREATED.load(Ordering::SeqCst), 0);
+
+    assert!(context.call::<LazyCountingService>().is_some());
+    assert!(context.try_call::<LazyCountingService>().is_ok());
+    context.call_map(|_: &mut LazyCountingService| ()).unwrap();
+    assert_eq!(LAZY_CREATED.load(Ordering::SeqCst), 1);
+    assert_eq!(LAZY_BUILT.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn register_lazy_creates_the_service_once_on_first_use() {
+    let context = RSContextBuilder::new().register_lazy::<LazyCountingService>().unwrap().build().await.unwrap();
+    assert!(context.contains::<LazyCountingService>());
+    assert_eq!(LAZY_CREATED.load(Ordering::SeqCst), 0);
+
+    // The sync `call` can't create it, but the async accessors do.
+    assert!(context.call::<LazyCountingService>().is_none());
+    context.call_map(|_: &mut LazyCountingService| ()).await.unwrap();
+    assert!(context.call::<LazyCountingService>().is_some());
+    assert!(context.call_lazy::<LazyCountingService>().await.is_some());
+    assert!(context.try_call::<LazyCountingService>().await.is_ok());
+    assert_eq!(LAZY_CREATED.load(Ordering::SeqCst), 1);
+    assert_eq!(LAZY_BUILT.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn register_lazy_reports_a_failed_hook() {
+    let context = RSContextBuilder::new().register_lazy::<LazyFailingService>().unwrap().build().unwrap();
+    assert!(context.check_all_health().is_ok());
+
+    assert_lazy_hook_failed(context.try_call::<LazyFailingService>());
+    assert_lazy_hook_failed(context.try_call::<LazyFailingService>());
+    assert_lazy_hook_failed(context.call_map(|_: &mut LazyFailingService| ()));
+    assert!(context.call::<LazyFailingService>().is_none());
+    let failures = context.check_all_health().unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_lazy_hook_failed::<()>(Err(failures[0].1.clone()));
+
+    let context = RSContextBuilder::new()
+        .map_hook_errors(|_, error| RsServiceError::Other(format!("[lazy] {error}")))
+        .register_lazy::<LazyFailingService>()
+        .and_then(|builder| builder.build())
+        .unwrap();
+    assert!(matches!(context.try_call::<LazyFailingService>(), Err(RsServiceError::Other(message)) if message.starts_with("[lazy]")));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn register_lazy_reports_a_failed_hook() {
+    let context = RSContextBuilder::new().register_lazy::<LazyFailingService>().unwrap().build().await.unwrap();
+    assert!(context.check_all_health().await.is_ok());
+
+    assert_lazy_hook_failed(context.try_call::<LazyFailingService>().await);
+    assert_lazy_hook_failed(context.try_call::<LazyFailingService>().await);
+    assert_lazy_hook_failed(context.call_map(|_: &mut LazyFailingService| ()).await);
+    assert!(context.call_lazy::<LazyFailingService>().await.is_none());
+    let failures = context.check_all_health().await.unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_lazy_hook_failed::<()>(Err(failures[0].1.clone()));
+
+    let context = RSContextBuilder::new()
+        .map_hook_errors(|_, error| RsServiceError::Other(format!("[lazy] {error}")))
+        .register_lazy::<LazyFailingService>()
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert!(matches!(context.try_call::<LazyFailingService>().await, Err(RsServiceError::Other(message)) if message.starts_with("[lazy]")));
+}
+
+#[r_service_struct]
+struct LoggerService {
+    started: AtomicBool,
+}
+
+#[r_service_struct]
+struct StartupProbeService {
+    saw_logger: AtomicBool,
+}
+
+impl_rs_service! {
+    impl LoggerService {
+        fn on_register_crate_instance() -> Self {
+            LoggerService { started: AtomicBool::new(false) }
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            self.started.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+        fn priority() -> i32 {
+            100
+        }
+    }
+}
+
+// Written per backend: the async hook `.await`s LoggerService's lock.
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+impl RSContextService for StartupProbeService {
+    fn on_register_crate_instance() -> Self {
+        StartupProbeService { saw_logger: AtomicBool::new(false) }
+    }
+    fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+    fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+        let logger = context.call::<LoggerService>().ok_or(RsServiceError::NotFound("LoggerService".into()))?;
+        let started = lock(&logger).started.load(Ordering::SeqCst);
+        self.saw_logger.store(started, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl RSContextService for StartupProbeService {
+    type Error = RsServiceError;
+    async fn on_register_crate_instance() -> Self {
+        StartupProbeService { saw_logger: AtomicBool::new(false) }
+    }
+    async fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+    async fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+        let logger = context.call::<LoggerService>().ok_or(RsServiceError::NotFound("LoggerService".into()))?;
+        let started = logger.lock().await.started.load(Ordering::SeqCst);
+        self.saw_logger.store(started, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn higher_priority_hooks_run_first() {
+    let context = RSContextBuilder::new()
+        .register::<StartupProbeService>()
+        .and_then(|builder| builder.register::<LoggerService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    let probe = context.call::<StartupProbeService>().unwrap();
+    assert!(lock(&probe).saw_logger.load(Ordering::SeqCst));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn higher_priority_hooks_run_first() {
+    let context = RSContextBuilder::new()
+        .register::<StartupProbeService>()
+        .await
+        .unwrap()
+        .register::<LoggerService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    let probe = context.call::<StartupProbeService>().unwrap();
+    assert!(probe.lock().await.saw_logger.load(Ordering::SeqCst));
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn post_build_hooks_can_run_later() {
+    let context = RSContextBuilder::new()
+        .register::<LoggerService>()
+        .and_then(|builder| builder.build_without_hooks())
+        .unwrap();
+    let logger = context.call::<LoggerService>().unwrap();
+    assert!(!lock(&logger).started.load(Ordering::SeqCst));
+
+    context.run_post_build_hooks().unwrap();
+    assert!(lock(&logger).started.load(Ordering::SeqCst));
+    context.run_post_build_hooks().unwrap();
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn post_build_hooks_can_run_later() {
+    let context = RSContextBuilder::new()
+        .register::<LoggerService>()
+        .await
+        .unwrap()
+        .build_without_hooks()
+        .await
+        .unwrap();
+    let logger = context.call::<LoggerService>().unwrap();
+    assert!(!logger.lock().await.started.load(Ordering::SeqCst));
+
+    context.run_post_build_hooks().await.unwrap();
+    assert!(logger.lock().await.started.load(Ordering::SeqCst));
+    context.run_post_build_hooks().await.unwrap();
+}
+
+#[r_service_struct]
+struct RegistryObserverService {
+    peers_seen: AtomicUsize,
+}
+
+impl RegistryObserverService {
+    fn observe(&self, context: &RSContext) {
+        if context.call::<CounterService>().is_some() {
+            self.peers_seen.store(context.get_metadata().len(), Ordering::SeqCst);
+        }
+    }
+}
+
+impl_rs_service! {
+    impl RegistryObserverService {
+        fn on_register_crate_instance() -> Self {
+            RegistryObserverService { peers_seen: AtomicUsize::new(0) }
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+            self.observe(context);
+            Ok(())
+        }
+    }
+}
+
+// The observer is registered first, so its hook runs before any other service's.
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn hooks_see_the_complete_registry() {
+    let context = RSContextBuilder::new()
+        .register::<RegistryObserverService>()
+        .and_then(|builder| builder.register::<CounterService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    let observer = context.call::<RegistryObserverService>().unwrap();
+    assert_eq!(lock(&observer).peers_seen.load(Ordering::SeqCst), 2);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn hooks_see_the_complete_registry() {
+    let context = RSContextBuilder::new()
+        .register::<RegistryObserverService>()
+        .await
+        .unwrap()
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    let observer = context.call::<RegistryObserverService>().unwrap();
+    assert_eq!(observer.lock().await.peers_seen.load(Ordering::SeqCst), 2);
+}
+
+#[r_service_struct]
+struct UnhealthyService {}
+
+impl_rs_service! {
+    impl UnhealthyService {
+        fn on_register_crate_instance() -> Self {
+            UnhealthyService {}
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn health_check(&self) -> Result<(), RsServiceError> {
+            Err(RsServiceError::Other("database unreachable".to_string()))
+        }
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn check_all_health_reports_failing_services() {
+    let context = RSContextBuilder::new()
+        .register::<CounterService>()
+        .and_then(|builder| builder.register::<UnhealthyService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    let failures = context.check_all_health().unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, TypeId::of::<UnhealthyService>());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn check_all_health_reports_failing_services() {
+    let context = RSContextBuilder::new()
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .register::<UnhealthyService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    let failures = context.check_all_health().await.unwrap_err();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].0, TypeId::of::<UnhealthyService>());
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn register_with_uses_the_factory() {
+    let context = RSContextBuilder::new()
+        .register_with(|| PingService { pongs: 10 })
+        .and_then(|builder| builder.register::<PongService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    assert_eq!(context.call_map(|ping: &mut PingService| ping.pongs).unwrap(), 11);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn register_with_uses_the_factory() {
+    let context = RSContextBuilder::new()
+        .register_with(|| PingService { pongs: 10 })
+        .await
+        .unwrap()
+        .register::<PongService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(context.call_map(|ping: &mut PingService| ping.pongs).await.unwrap(), 11);
+}
+
+#[derive(Default, Clone, RSContextService)]
+struct DerivedService {
+    port: u16,
+}
+
+#[derive(Clone, RSContextService)]
+#[rservice(new, name = "derived-with-new")]
+struct DerivedWithNewService {
+    port: u16,
+}
+
+impl DerivedWithNewService {
+    fn new() -> Self {
+        DerivedWithNewService { port: 8080 }
+    }
+}
+
+#[r_service_struct]
+struct UnifiedService {
+    created: bool,
+}
+
+// Without auto_impl, #[r_service] leaves the trait to the hand-written impl below.
+#[r_service]
+impl UnifiedService {
+    pub fn new() -> Self {
+        UnifiedService { created: false }
+    }
+}
+
+impl_rs_service! {
+    impl UnifiedService {
+        fn on_register_crate_instance() -> Self {
+            UnifiedService::new()
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            self.created = true;
+            Ok(())
+        }
+        fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+            // call_weak, since tokio debug builds reject `call` for a service whose lock is held.
+            context.call_weak::<UnifiedService>().map(|_| ()).ok_or_else(|| RsServiceError::NotFound("UnifiedService".to_string()))
+        }
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn impl_rs_service_runs_the_hooks() {
+    let context = RSContextBuilder::new().register::<UnifiedService>().unwrap().build().unwrap();
+    assert!(lock(&context.call::<UnifiedService>().unwrap()).created);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn impl_rs_service_runs_the_hooks() {
+    let context = RSContextBuilder::new().register::<UnifiedService>().await.unwrap().build().await.unwrap();
+    assert!(context.call::<UnifiedService>().unwrap().lock().await.created);
+}
+
+// Address of the context its on_all_services_built hook ran against.
+static HOOKED_CONTEXT: AtomicUsize = AtomicUsize::new(0);
+
+#[r_service_struct]
+struct ContextAddressService {}
+
+impl_rs_service! {
+    impl ContextAddressService {
+        fn on_register_crate_instance() -> Self {
+            ContextAddressService {}
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+            HOOKED_CONTEXT.store(context as *const RSContext as usize, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn build_arc_runs_hooks_against_the_returned_context() {
+    let context = RSContextBuilder::new().register::<ContextAddressService>().unwrap().build_arc().unwrap();
+    assert_eq!(HOOKED_CONTEXT.load(Ordering::SeqCst), Arc::as_ptr(&context) as usize);
+    assert_eq!(Arc::strong_count(&context), 1);
+    assert!(context.call::<ContextAddressService>().is_some());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn build_arc_runs_hooks_against_the_returned_context() {
+    let context = RSContextBuilder::new().register::<ContextAddressService>().await.unwrap().build_arc().await.unwrap();
+    assert_eq!(HOOKED_CONTEXT.load(Ordering::SeqCst), Arc::as_ptr(&context) as usize);
+    assert_eq!(Arc::strong_count(&context), 1);
+    assert!(context.call::<ContextAddressService>().is_some());
+}
+
+#[derive(RSContextService)]
+#[rservice(new)]
+struct Cache<V: Send + Sync + 'static> {
+    entries: Vec<V>,
+}
+
+impl<V: Send + Sync + 'static> Cache<V> {
+    fn new() -> Self {
+        Cache { entries: Vec::new() }
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn generic_instantiations_are_distinct_services() {
+    let context = RSContextBuilder::new()
+        .register::<Cache<String>>()
+        .unwrap()
+        .register::<Cache<u64>>()
+        .unwrap()
+        .build()
+        .unwrap();
+
+    lock(&context.call::<Cache<String>>().unwrap()).entries.push("hit".to_string());
+    assert_eq!(lock(&context.call::<Cache<String>>().unwrap()).entries.len(), 1);
+    assert!(lock(&context.call::<Cache<u64>>().unwrap()).entries.is_empty());
+    assert_eq!(context.len(), 2);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn generic_instantiations_are_distinct_services() {
+    let context = RSContextBuilder::new()
+        .register::<Cache<String>>()
+        .await
+        .unwrap()
+        .register::<Cache<u64>>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    context.call::<Cache<String>>().unwrap().lock().await.entries.push("hit".to_string());
+    assert_eq!(context.call::<Cache<String>>().unwrap().lock().await.entries.len(), 1);
+    assert!(context.call::<Cache<u64>>().unwrap().lock().await.entries.is_empty());
+    assert_eq!(context.len(), 2);
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn derived_services_use_default_or_new() {
+    let context = RSContextBuilder::new()
+        .register::<DerivedService>()
+        .and_then(|builder| builder.register::<DerivedWithNewService>())
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    assert_eq!(context.call_map(|service: &mut DerivedService| service.port).unwrap(), 0);
+    assert_eq!(context.call_map(|service: &mut DerivedWithNewService| service.port).unwrap(), 8080);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn derived_services_use_default_or_new() {
+    let context = RSContextBuilder::new()
+        .register::<DerivedService>()
+        .await
+        .unwrap()
+        .register::<DerivedWithNewService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(context.call_map(|service: &mut DerivedService| service.port).await.unwrap(), 0);
+    assert_eq!(context.call_map(|service: &mut DerivedWithNewService| service.port).await.unwrap(), 8080);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn call_map_async_holds_the_lock_across_await() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+
+    let port = context.call_map_async(async |service: &mut DerivedService| {
+        service.port += 1;
+        std::future::ready(()).await;
+        service.port
+    }).await;
+    assert_eq!(port.unwrap(), 81);
+    assert!(matches!(
+        context.call_map_async(async |_: &mut CounterService| ()).await,
+        Err(RsServiceError::NotFound(_))
+    ));
+}
+
+#[cfg(all(feature = "tokio", debug_assertions))]
+#[tokio::test]
+#[should_panic(expected = "re-entrant lock of service")]
+async fn relocking_a_held_service_panics_in_debug() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+
+    let _ = context.call_map_async(async |_: &mut DerivedService| {
+        context.call_map(|service: &mut DerivedService| service.port).await
+    }).await;
+}
+
+// The service asks the context for itself while its own lock is held, then locks it.
+#[cfg(all(feature = "tokio", debug_assertions))]
+#[tokio::test]
+#[should_panic(expected = "re-entrant lock of service")]
+async fn calling_a_held_service_panics_in_debug() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+
+    let _ = context.call_map_async(async |_: &mut DerivedService| {
+        context.call::<DerivedService>().unwrap().lock().await.port
+    }).await;
+}
+
+#[cfg(all(feature = "tokio", debug_assertions))]
+#[tokio::test]
+#[should_panic(expected = "re-entrant lock of service")]
+async fn health_check_of_a_held_service_panics_in_debug() {
+    let context = RSContext::with_service(DerivedService { port: 80 });
+
+    let _ = context.call_map_async(async |_: &mut DerivedService| {
+        context.check_all_health().await
+    }).await;
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn shared_services_are_stored_without_a_mutex() {
+    let context = RSContextBuilder::new()
+        .register_shared::<DerivedWithNewService>()
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    assert_eq!(context.call_shared::<DerivedWithNewService>().unwrap().port, 8080);
+    assert!(context.call::<DerivedWithNewService>().is_none());
+    assert!(context.check_all_health().is_ok());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn shared_services_are_stored_without_a_mutex() {
+    let context = RSContextBuilder::new()
+        .register_shared::<DerivedWithNewService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(context.call_shared::<DerivedWithNewService>().unwrap().port, 8080);
+    assert!(context.call::<DerivedWithNewService>().is_none());
+    assert!(context.check_all_health().await.is_ok());
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn reload_recreates_the_service_in_place() {
+    let context = RSContext::with_service(DerivedWithNewService { port: 1 });
+    let held = context.call::<DerivedWithNewService>().unwrap();
+
+    context.reload::<DerivedWithNewService>().unwrap();
+    assert_eq!(lock(&held).port, 8080);
+    assert!(matches!(context.reload::<DerivedService>(), Err(RsServiceError::NotFound(_))));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn reload_recreates_the_service_in_place() {
+    let context = RSContext::with_service(DerivedWithNewService { port: 1 });
+    let held = context.call::<DerivedWithNewService>().unwrap();
+
+    context.reload::<DerivedWithNewService>().await.unwrap();
+    assert_eq!(held.lock().await.port, 8080);
+    assert!(matches!(context.reload::<DerivedService>().await, Err(RsServiceError::NotFound(_))));
+}
+
+// reload runs its hooks with the builder's settings, like lazy services do.
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "spin")))]
+#[test]
+fn reload_catches_panics_and_maps_hook_errors() {
+    let context = RSContextBuilder::new()
+        .map_hook_errors(|_, error| RsServiceError::Other(format!("[reload] {error}")))
+        .register_instance(PanickingConstructorService {})
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    let result = context.reload::<PanickingConstructorService>();
+    assert!(matches!(result, Err(RsServiceError::Other(message)) if message.starts_with("[reload]") && message.contains("no config")));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn reload_catches_panics_and_maps_hook_errors() {
+    let context = RSContextBuilder::new()
+        .map_hook_errors(|_, error| RsServiceError::Other(format!("[reload] {error}")))
+        .register_instance(PanickingConstructorService {})
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    let result = context.reload::<PanickingConstructorService>().await;
+    assert!(matches!(result, Err(RsServiceError::Other(message)) if message.starts_with("[reload]") && message.contains("no config")));
+}
+
+fn tag_hook_error(type_id: TypeId, error: RsServiceError) -> RsServiceError {
+    assert_eq!(type_id, TypeId::of::<FailingService>());
+    RsServiceError::Other(format!("[request 42] {}", error))
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn map_hook_errors_rewrites_hook_failures() {
+    let result = RSContextBuilder::new()
+        .map_hook_errors(tag_hook_error)
+        .register::<FailingService>();
+
+    assert!(matches!(result, Err(RsServiceError::Other(message)) if message.starts_with("[request 42]")));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn map_hook_errors_rewrites_hook_failures() {
+    let result = RSContextBuilder::new()
+        .map_hook_errors(tag_hook_error)
+        .register::<FailingService>()
+        .await;
+
+    assert!(matches!(result, Err(RsServiceError::Other(message)) if message.starts_with("[request 42]")));
+}
+
+trait Greeting {
+    fn greet(&self) -> String;
+}
+
+struct EnglishGreeting {}
+
+impl Greeting for EnglishGreeting {
+    fn greet(&self) -> String {
+        "hello".to_string()
+    }
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn trait_objects_are_registered_by_trait() {
+    let builder = RSContextBuilder::new()
+        .register_dyn::<dyn Greeting + Send>(Box::new(EnglishGreeting {}))
+        .unwrap();
+    assert!(matches!(
+        builder.register_dyn::<dyn Greeting + Send>(Box::new(EnglishGreeting {})),
+        Err(RsServiceError::AlreadyRegistered(_))
+    ));
+
+    let context = RSContextBuilder::new()
+        .register_dyn::<dyn Greeting + Send>(Box::new(EnglishGreeting {}))
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(lock(&context.call_dyn::<dyn Greeting + Send>().unwrap()).greet(), "hello");
+    assert!(context.call_dyn::<dyn Greeting + Send + Sync>().is_none());
+
+    let child = context
+        .child()
+        .register_dyn::<dyn Greeting + Send>(Box::new(EnglishGreeting {}))
+        .unwrap()
+        .build()
+        .unwrap();
+    assert_eq!(child.iter_services_by_trait::<dyn Greeting + Send>().count(), 2);
+    assert_eq!(context.iter_services_by_trait::<dyn Greeting + Send>().count(), 1);
+    assert_eq!(child.iter_services_by_trait::<dyn Greeting + Send + Sync>().count(), 0);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn trait_objects_are_registered_by_trait() {
+    let builder = RSContextBuilder::new()
+        .register_dyn::<dyn Greeting + Send>(Box::new(EnglishGreeting {}))
+        .unwrap();
+    assert!(matches!(
+        builder.register_dyn::<dyn Greeting + Send>(Box::new(EnglishGreeting {})),
+        Err(RsServiceError::AlreadyRegistered(_))
+    ));
+
+    let context = RSContextBuilder::new()
+        .register_dyn::<dyn Greeting + Send>(Box::new(EnglishGreeting {}))
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(context.call_dyn::<dyn Greeting + Send>().unwrap().lock().await.greet(), "hello");
+    assert!(context.call_dyn::<dyn Greeting + Send + Sync>().is_none());
+
+    let child = context
+        .child()
+        .register_dyn::<dyn Greeting + Send>(Box::new(EnglishGreeting {}))
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(child.iter_services_by_trait::<dyn Greeting + Send>().count(), 2);
+    assert_eq!(context.iter_services_by_trait::<dyn Greeting + Send>().count(), 1);
+    assert_eq!(child.iter_services_by_trait::<dyn Greeting + Send + Sync>().count(), 0);
+}
+
+fn assert_greeter_is_callable(context: &RSContext) {
+    let greet = context.call_fn::<&str, String>("greet").unwrap();
+    assert_eq!(greet("rs"), "hello, rs");
+    assert!(context.call_fn::<u32, String>("greet").is_none());
+    assert!(context.call_fn::<&str, String>("missing").is_none());
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn functions_are_registered_by_name() {
+    let builder = RSContextBuilder::new()
+        .register_from_fn("greet", |name: &str| format!("hello, {name}"))
+        .unwrap();
+    assert!(matches!(
+        builder.register_from_fn("greet", |name: &str| name.len()),
+        Err(RsServiceError::AlreadyRegistered(_))
+    ));
+
+    let context = RSContextBuilder::new()
+        .register_from_fn("greet", |name: &str| format!("hello, {name}"))
+        .and_then(|builder| builder.build())
+        .unwrap();
+    assert_greeter_is_callable(&context);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn functions_are_registered_by_name() {
+    let builder = RSContextBuilder::new()
+        .register_from_fn("greet", |name: &str| format!("hello, {name}"))
+        .unwrap();
+    assert!(matches!(
+        builder.register_from_fn("greet", |name: &str| name.len()),
+        Err(RsServiceError::AlreadyRegistered(_))
+    ));
+
+    let context = RSContextBuilder::new()
+        .register_from_fn("greet", |name: &str| format!("hello, {name}"))
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert_greeter_is_callable(&context);
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn require_non_empty_rejects_empty_builds() {
+    assert!(matches!(RSContextBuilder::new().require_non_empty().build(), Err(RsServiceError::Other(_))));
+    assert!(RSContextBuilder::new().build().is_ok());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn require_non_empty_rejects_empty_builds() {
+    assert!(matches!(RSContextBuilder::new().require_non_empty().build().await, Err(RsServiceError::Other(_))));
+    assert!(RSContextBuilder::new().build().await.is_ok());
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn category_type_is_exposed() {
+    let context = RSContextBuilder::new()
+        .set_category(8080u16)
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    assert_eq!(context.category_type_id(), TypeId::of::<u16>());
+    assert_eq!(context.get_category::<u16>(), Some(&8080));
+    assert_eq!(RSContext::default().category_type_id(), TypeId::of::<()>());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn category_type_is_exposed() {
+    let context = RSContextBuilder::new()
+        .set_category(8080u16)
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(context.category_type_id(), TypeId::of::<u16>());
+    assert_eq!(context.get_category::<u16>(), Some(&8080));
+    assert_eq!(RSContext::default().category_type_id(), TypeId::of::<()>());
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn category_can_be_changed_during_registration() {
+    let mut builder = RSContextBuilder::new().set_category(vec!["metrics"]).unwrap();
+    assert_eq!(builder.with_category_mut(|flags: &mut Vec<&str>| flags.push("tracing")), Some(()));
+    assert_eq!(builder.with_category_mut(|port: &mut u16| *port), None);
+
+    let context = builder.build().unwrap();
+    assert_eq!(context.get_category::<Vec<&str>>(), Some(&vec!["metrics", "tracing"]));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn category_can_be_changed_during_registration() {
+    let mut builder = RSContextBuilder::new().set_category(vec!["metrics"]).unwrap();
+    assert_eq!(builder.with_category_mut(|flags: &mut Vec<&str>| flags.push("tracing")), Some(()));
+    assert_eq!(builder.with_category_mut(|port: &mut u16| *port), None);
+
+    let context = builder.build().await.unwrap();
+    assert_eq!(context.get_category::<Vec<&str>>(), Some(&vec!["metrics", "tracing"]));
+}
+
+#[cfg(feature = "serde")]
+type JsonCategory = std::collections::BTreeMap<String, u16>;
+
+#[cfg(feature = "serde")]
+fn assert_category_round_trips(context: &RSContext) {
+    assert_eq!(context.get_category::<JsonCategory>().map(|c| c["port"]), Some(8080));
+    assert_eq!(context.category_to_json().as_deref(), Some(r#"{"port":8080}"#));
+    assert_eq!(RSContext::default().category_to_json(), None);
+}
+
+#[cfg(all(feature = "serde", not(any(feature = "tokio", feature = "async-std"))))]
+#[test]
+fn category_round_trips_through_json() {
+    assert!(RSContextBuilder::new().set_category_json::<JsonCategory>("not json").is_err());
+    let context = RSContextBuilder::new()
+        .set_category_json::<JsonCategory>(r#"{"port":8080}"#)
+        .and_then(|builder| builder.build())
+        .unwrap();
+    assert_category_round_trips(&context);
+}
+
+#[cfg(all(feature = "serde", any(feature = "tokio", feature = "async-std")))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn category_round_trips_through_json() {
+    assert!(RSContextBuilder::new().set_category_json::<JsonCategory>("not json").is_err());
+    let context = RSContextBuilder::new()
+        .set_category_json::<JsonCategory>(r#"{"port":8080}"#)
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert_category_round_trips(&context);
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn extend_applies_every_registration() {
+    let plugins: Vec<Registration> = vec![
+        Box::new(|builder| builder.register::<CounterService>()),
+        Box::new(|builder| builder.register::<TrafficLight>()),
+    ];
+    let context = RSContextBuilder::new()
+        .extend(plugins)
+        .and_then(|builder| builder.build())
+        .unwrap();
+
+    assert_eq!(context.len(), 2);    assert!(context.call2::<CounterService, TrafficLight>().is_some());
+    assert!(context.call3::<CounterService, TrafficLight, PingService>().is_none());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn extend_applies_every_registration() {
+    let plugins: Vec<Registration> = vec![
+        Box::new(|builder| Box::pin(builder.register::<CounterService>())),
+        Box::new(|builder| Box::pin(builder.register::<TrafficLight>())),
+    ];
+    let context = RSContextBuilder::new()
+        .extend(plugins)
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+
+    assert_eq!(context.len(), 2);    assert!(context.call2::<CounterService, TrafficLight>().is_some());
+    assert!(context.call3::<CounterService, TrafficLight, PingService>().is_none());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[r_service_struct]
+struct SlowService {}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl RSContextService for SlowService {
+    type Error = RsServiceError;
+    async fn on_register_crate_instance() -> Self {
+        SlowService {}
+    }
+    async fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+    async fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+        #[cfg(feature = "tokio")]
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+        async_std::task::sleep(std::time::Duration::from_secs(60)).await;
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn build_with_timeout_stops_hanging_hooks() {
+    let result = RSContextBuilder::new()
+        .register::<SlowService>()
+        .await
+        .unwrap()
+        .build_with_timeout(std::time::Duration::from_millis(10))
+        .await;
+
+    assert!(matches!(result, Err(RsServiceError::Timeout(_))));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[r_service_struct]
+struct SlowConnectService {}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl RSContextService for SlowConnectService {
+    type Error = RsServiceError;
+    async fn on_register_crate_instance() -> Self {
+        SlowConnectService {}
+    }
+    async fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        #[cfg(feature = "tokio")]
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+        async_std::task::sleep(std::time::Duration::from_secs(60)).await;
+        Ok(())
+    }
+    async fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn register_with_timeout_names_the_slow_service() {
+    let result = RSContextBuilder::new()
+        .register_with_timeout::<SlowConnectService>(std::time::Duration::from_millis(10))
+        .await;
+    assert!(matches!(result, Err(RsServiceError::Timeout(message)) if message.contains("SlowConnectService")));
+
+    let builder = RSContextBuilder::new()
+        .register_with_timeout::<CounterService>(std::time::Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert!(builder.build().await.unwrap().call::<CounterService>().is_some());
+}
+
+/// Requests PingService and then hangs, once CounterService is registered.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[r_service_struct]
+struct SlowRequestingService {}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl RSContextService for SlowRequestingService {
+    type Error = RsServiceError;
+    async fn on_register_crate_instance() -> Self {
+        SlowRequestingService {}
+    }
+    async fn on_service_created(&mut self, builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        builder.request_register::<PingService>();
+        #[cfg(feature = "tokio")]
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+        async_std::task::sleep(std::time::Duration::from_secs(60)).await;
+        Ok(())
+    }
+    async fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+    fn dependencies() -> Vec<TypeId> {
+        vec![TypeId::of::<CounterService>()]
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn register_with_timeout_drops_requests_of_the_timed_out_hook() {
+    let mut builder = RSContextBuilder::new()
+        .register_with_timeout::<SlowRequestingService>(std::time::Duration::from_millis(10))
+        .await
+        .unwrap();
+    // CounterService releases the deferred SlowRequestingService, which times out.
+    assert!(matches!(builder.try_register::<CounterService>().await, Err(RsServiceError::Timeout(_))));
+
+    // Registering afterwards must not pick up the PingService its hook requested.
+    builder.try_register::<TrafficLight>().await.unwrap();
+    assert!(builder.call_pending::<PingService>().is_none());
+    assert!(builder.call_pending::<TrafficLight>().is_some());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+static DATABASE_CONNECT_ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Fails to connect until its third attempt, like a database that is still starting,
+/// warning about each attempt.
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[r_service_struct]
+struct FlakyDatabaseService {}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl RSContextService for FlakyDatabaseService {
+    type Error = RsServiceError;
+    async fn on_register_crate_instance() -> Self {
+        FlakyDatabaseService {}
+    }
+    async fn on_service_created(&mut self, builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        let attempt = DATABASE_CONNECT_ATTEMPTS.fetch_add(1, Ordering::SeqCst);
+        builder.warn(format!("connecting to the database, attempt {}", attempt + 1));
+        if attempt < 2 {
+            return Err(RsServiceError::Other("connection refused".to_string()));
+        }
+        Ok(())
+    }
+    async fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn register_with_retry_outlasts_transient_failures() {
+    let backoff = std::time::Duration::from_millis(1);
+    let builder = RSContextBuilder::new()
+        .register_with_retry::<FlakyDatabaseService>(3, backoff)
+        .await
+        .unwrap();
+    assert_eq!(DATABASE_CONNECT_ATTEMPTS.load(Ordering::SeqCst), 3);
+    // Only the warning of the attempt that succeeded is kept.
+    let (context, warnings) = builder.build_with_warnings().await.unwrap();
+    assert!(context.call::<FlakyDatabaseService>().is_some());
+    assert_eq!(warnings, ["connecting to the database, attempt 3"]);
+
+    let err = RSContextBuilder::new().register_with_retry::<FailingService>(2, backoff).await.err().unwrap();
+    let RsServiceError::RetriesExhausted { attempts, source, .. } = &err else {
+        panic!("expected RetriesExhausted, got {err:?}");
+    };
+    assert_eq!(*attempts, 2);
+    assert!(err.to_string().contains("after 2 attempts"));
+    assert_hook_failure_keeps_source(Err(source.as_ref().clone()));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn build_streaming_reports_progress() {
+    use futures_core::Stream;
+    use rs_ervice::BuildEvent;
+
+    let mut stream = RSContextBuilder::new()
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .build_streaming();
+    let mut events = Vec::new();
+    while let Some(event) = std::future::poll_fn(|cx| std::pin::Pin::new(&mut stream).poll_next(cx)).await {
+        events.push(event);
+    }
+
+    let counter = TypeId::of::<CounterService>();
+    assert!(matches!(
+        events.as_slice(),
+        [BuildEvent::Registered(registered), BuildEvent::HookStarted(started), BuildEvent::HookFinished(finished), BuildEvent::Built(Ok(context))]
+            if [*registered, *started, *finished] == [counter; 3] && context.call::<CounterService>().is_some()
+    ));
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+static BACKGROUND_TASK_SAW_CONTEXT: AtomicBool = AtomicBool::new(false);
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[r_service_struct]
+struct BackgroundTaskService {}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl RSContextService for BackgroundTaskService {
+    type Error = RsServiceError;
+    async fn on_register_crate_instance() -> Self {
+        BackgroundTaskService {}
+    }
+    async fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+        Ok(())
+    }
+    async fn on_all_services_built(&self, context: &RSContext) -> Result<(), RsServiceError> {
+        // The task outlives the hook, keeping its own handle to the context.
+        let context = Arc::new(context.clone());
+        let task = async move {
+            if context.call::<CounterService>().is_some() {
+                BACKGROUND_TASK_SAW_CONTEXT.store(true, Ordering::SeqCst);
+            }
+        };
+        #[cfg(feature = "tokio")]
+        tokio::spawn(task);
+        #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+        async_std::task::spawn(task);
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn build_succeeds_while_a_hook_task_holds_the_context() {
+    let context = RSContextBuilder::new()
+        .register::<CounterService>()
+        .await
+        .unwrap()
+        .register::<BackgroundTaskService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert!(context.call::<BackgroundTaskService>().is_some());
+
+    for _ in 0..100 {
+        if BACKGROUND_TASK_SAW_CONTEXT.load(Ordering::SeqCst) {
+            return;
+        }
+        #[cfg(feature = "tokio")]
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        #[cfg(all(feature = "async-std", not(feature = "tokio")))]
+        async_std::task::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    panic!("the task spawned by the hook never saw the context");
+}
+
+fn assert_factory_error_names_service(result: Result<RSContextBuilder, RsServiceError>) {
+    let err = result.err().expect("the factory should fail the registration");
+    assert!(matches!(
+        err,
+        RsServiceError::HookFailed { hook: "on_register_crate_instance", type_id, .. } if type_id == TypeId::of::<DerivedService>()
+    ));
+    assert!(err.source().unwrap().to_string().contains("no port configured"));
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn register_fallible_propagates_factory_errors() {
+    assert_factory_error_names_service(
+        RSContextBuilder::new().register_fallible::<DerivedService>(|| Err(RsServiceError::Other("no port configured".to_string()))),
+    );
+    let context = RSContextBuilder::new()
+        .register_fallible(|| Ok(DerivedService { port: 443 }))
+        .and_then(|builder| builder.build())
+        .unwrap();
+    assert_eq!(lock(&context.call::<DerivedService>().unwrap()).port, 443);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn register_fallible_propagates_factory_errors() {
+    assert_factory_error_names_service(
+        RSContextBuilder::new()
+            .register_fallible::<DerivedService>(|| Err(RsServiceError::Other("no port configured".to_string())))
+            .await,
+    );
+    let context = RSContextBuilder::new()
+        .register_fallible(|| Ok(DerivedService { port: 443 }))
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(context.call::<DerivedService>().unwrap().lock().await.port, 443);
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[derive(Debug)]
+struct ConfigError(&'static str);
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl From<ConfigError> for RsServiceError {
+    fn from(e: ConfigError) -> Self {
+        RsServiceError::Other(format!("config: {}", e.0))
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[r_service_struct]
+struct MisconfiguredService {}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+impl RSContextService for MisconfiguredService {
+    type Error = ConfigError;
+    async fn on_register_crate_instance() -> Self {
+        MisconfiguredService {}
+    }
+    async fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), ConfigError> {
+        Err(ConfigError("missing port"))
+    }
+    async fn on_all_services_built(&self, _context: &RSContext) -> Result<(), ConfigError> {
+        Ok(())
+    }
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn hook_domain_errors_are_converted() {
+    let err = RSContextBuilder::new().register::<MisconfiguredService>().await.err().unwrap();
+
+    assert!(matches!(err, RsServiceError::HookFailed { hook: "on_service_created", .. }));
+    assert_eq!(err.source().unwrap().to_string(), RsServiceError::from(ConfigError("missing port")).to_string());
+}
+
+#[r_service_struct]
+struct PanickingService {}
+
+impl_rs_service! {
+    impl PanickingService {
+        fn on_register_crate_instance() -> Self {
+            PanickingService {}
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            panic!("kaboom")
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "spin"))]
+fn assert_panic_names_service(result: Result<RSContextBuilder, RsServiceError>) {
+    let err = result.err().expect("PanickingService should fail to register");
+    assert!(matches!(
+        err,
+        RsServiceError::HookFailed { hook: "on_service_created", type_id, .. } if type_id == TypeId::of::<PanickingService>()
+    ));
+    assert!(err.source().unwrap().to_string().ends_with("hook panicked: kaboom"));
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+fn hook_panics_become_errors() {
+    #[cfg(not(feature = "spin"))]
+    assert_panic_names_service(RSContextBuilder::new().register::<PanickingService>());
+    // Panics can't be caught in the no_std spin build, so they unwind there.
+    #[cfg(feature = "spin")]
+    assert!(std::panic::catch_unwind(|| RSContextBuilder::new().register::<PanickingService>()).is_err());
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn hook_panics_become_errors() {
+    assert_panic_names_service(RSContextBuilder::new().register::<PanickingService>().await);
+}
+
+#[cfg(not(any(feature = "tokio", feature = "async-std")))]
+#[test]
+#[should_panic(expected = "kaboom")]
+fn hook_panics_propagate_without_catch_panics() {
+    let _ = RSContextBuilder::new().catch_panics(false).register::<PanickingService>();
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+#[should_panic(expected = "kaboom")]
+async fn hook_panics_propagate_without_catch_panics() {
+    let _ = RSContextBuilder::new().catch_panics(false).register::<PanickingService>().await;
+}
+
+#[cfg(not(feature = "spin"))]
+#[r_service_struct]
+struct PanickingConstructorService {}
+
+#[cfg(not(feature = "spin"))]
+impl_rs_service! {
+    impl PanickingConstructorService {
+        fn on_register_crate_instance() -> Self {
+            panic!("no config")
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "spin"))]
+#[r_service_struct]
+struct PanickingHealthService {}
+
+#[cfg(not(feature = "spin"))]
+impl_rs_service! {
+    impl PanickingHealthService {
+        fn on_register_crate_instance() -> Self {
+            PanickingHealthService {}
+        }
+        fn on_service_created(&mut self, _builder: &mut RSContextBuilder) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn on_all_services_built(&self, _context: &RSContext) -> Result<(), RsServiceError> {
+            Ok(())
+        }
+        fn health_check(&self) -> Result<(), RsServiceError> {
+            panic!("probe crashed")
+        }
+    }
+}
+
+#[cfg(not(feature = "spin"))]
+fn assert_panic_in_hook<T: 'static, R>(result: Result<R, RsServiceError>, hook: &str, message: &str) {
+    let err = result.err().expect("the panicking hook should fail");
+    assert!(matches!(
+        &err,
+        RsServiceError::HookFailed { hook: failed, type_id, .. } if *failed == hook && *type_id == TypeId::of::<T>()
+    ));
+    assert!(err.source().unwrap().to_string().ends_with(message));
+}
+
+// Panics can't be caught in the no_std spin build.
+#[cfg(not(any(feature = "tokio", feature = "async-std", feature = "spin")))]
+#[test]
+fn constructor_and_health_check_panics_become_errors() {
+    let result = RSContextBuilder::new().register::<PanickingConstructorService>();
+    assert_panic_in_hook::<PanickingConstructorService, _>(result, "on_register_crate_instance", "no config");
+    let result = RSContextBuilder::new().register_with::<CounterService>(|| panic!("no factory"));
+    assert_panic_in_hook::<CounterService, _>(result, "on_register_crate_instance", "no factory");
+
+    let context = RSContextBuilder::new()
+        .register::<PanickingHealthService>()
+        .and_then(|builder| builder.build())
+        .unwrap();
+    let errors = context.check_all_health().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_panic_in_hook::<PanickingHealthService, ()>(Err(errors[0].1.clone()), "health_check", "probe crashed");
+}
+
+#[cfg(any(feature = "tokio", feature = "async-std"))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn constructor_and_health_check_panics_become_errors() {
+    let result = RSContextBuilder::new().register::<PanickingConstructorService>().await;
+    assert_panic_in_hook::<PanickingConstructorService, _>(result, "on_register_crate_instance", "no config");
+    let result = RSContextBuilder::new().register_with::<CounterService>(|| panic!("no factory")).await;
+    assert_panic_in_hook::<CounterService, _>(result, "on_register_crate_instance", "no factory");
+
+    let context = RSContextBuilder::new()
+        .register::<PanickingHealthService>()
+        .await
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let errors = context.check_all_health().await.unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_panic_in_hook::<PanickingHealthService, ()>(Err(errors[0].1.clone()), "health_check", "probe crashed");
+}
+
+// Records the `step` and `service` fields of every `rs_ervice` span opened while it is the default subscriber.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+struct SpanCapture(Arc<std::sync::Mutex<Vec<(String, String)>>>);
+
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct SpanFields {
+    step: String,
+    service: String,
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for SpanFields {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        match field.name() {
+            "step" => self.step = value.to_string(),
+            "service" => self.service = value.to_string(),
+            _ => {}
+        }
+    }
+    fn record_debug(&mut self, _: &tracing::field::Field, _: &dyn std::fmt::Debug) {}
+}
+
+#[cfg(feature = "tracing")]
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for SpanCapture {
+    fn on_new_span(&self, attrs: &tracing::span::Attributes<'_>, _: &tracing::span::Id, _: tracing_subscriber::layer::Context<'_, S>) {
+        if attrs.metadata().name() == "rs_ervice" {
+            let mut fields = SpanFields::default();
+            attrs.record(&mut fields);
+            self.0.lock().unwrap().push((fields.step, fields.service));
+        }
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl SpanCapture {
+    fn install(&self) -> tracing::subscriber::DefaultGuard {
+        use tracing_subscriber::layer::SubscriberExt;
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(self.clone()))
+    }
+
+    fn assert_spans_for<T: RSContextService>(&self, steps: &[&str]) {
+        let spans = self.0.lock().unwrap();
+        for step in steps {
+            assert!(
+                spans.iter().any(|(span_step, service)| span_step == step && service == T::name()),
+                "no {step} span for {} in {spans:?}", T::name()
+            );
+        }
+    }
+}
+
+#[cfg(all(feature = "tracing", not(any(feature = "tokio", feature = "async-std"))))]
+#[test]
+fn tracing_spans_carry_the_service_name() {
+    let capture = SpanCapture::default();
+    let _guard = capture.install();
+    RSContextBuilder::new().register::<DerivedService>().unwrap().build().unwrap();
+    capture.assert_spans_for::<DerivedService>(&["register", "on_register_crate_instance", "on_service_created"]);
+}
+
+#[cfg(all(feature = "tracing", any(feature = "tokio", feature = "async-std")))]
+#[cfg_attr(feature = "tokio", tokio::test)]
+#[cfg_attr(feature = "async-std", async_std::test)]
+async fn tracing_spans_carry_the_service_name() {
+    let capture = SpanCapture::default();
+    let _guard = capture.install();
+    RSContextBuilder::new().register::<DerivedService>().await.unwrap().build().await.unwrap();
+    capture.assert_spans_for::<DerivedService>(&["register", "on_register_crate_instance", "on_service_created"]);
+}